@@ -0,0 +1,91 @@
+use super::{linked_list::LinkedListAllocator, Locked};
+use alloc::alloc::{GlobalAlloc, Layout};
+use core::mem;
+
+/// Block sizes served from the free lists; each must be a power of two
+/// because it is also used as the block alignment.
+const BLOCK_SIZES: &[usize] = &[8, 16, 32, 64, 128, 256, 512, 1024, 2048];
+
+/// A free block, stored inside the block itself.
+struct ListNode {
+    next: Option<&'static mut ListNode>,
+}
+
+/// An allocator that serves small allocations from per-size free lists and
+/// falls back to a linked list allocator for everything else.
+pub struct FixedSizeBlockAllocator {
+    list_heads: [Option<&'static mut ListNode>; BLOCK_SIZES.len()],
+    fallback_allocator: LinkedListAllocator,
+}
+
+impl FixedSizeBlockAllocator {
+    /// Creates an empty allocator.
+    pub const fn new() -> Self {
+        const EMPTY: Option<&'static mut ListNode> = None;
+        FixedSizeBlockAllocator {
+            list_heads: [EMPTY; BLOCK_SIZES.len()],
+            fallback_allocator: LinkedListAllocator::new(),
+        }
+    }
+
+    /// Initializes the allocator with the given heap bounds.
+    ///
+    /// # Safety
+    /// The memory range must be unused and this must only be called once.
+    pub unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        unsafe { self.fallback_allocator.init(heap_start, heap_size) };
+    }
+
+    /// Allocates using the fallback allocator.
+    fn fallback_alloc(&mut self, layout: Layout) -> *mut u8 {
+        self.fallback_allocator.allocate(layout)
+    }
+}
+
+/// Chooses the index of the smallest block size that fits the given layout.
+fn list_index(layout: &Layout) -> Option<usize> {
+    let required_block_size = layout.size().max(layout.align());
+    BLOCK_SIZES.iter().position(|&s| s >= required_block_size)
+}
+
+unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut allocator = self.lock();
+        match list_index(&layout) {
+            Some(index) => match allocator.list_heads[index].take() {
+                Some(node) => {
+                    allocator.list_heads[index] = node.next.take();
+                    node as *mut ListNode as *mut u8
+                }
+                None => {
+                    // No free block of this size yet, carve a new one.
+                    let block_size = BLOCK_SIZES[index];
+                    let block_align = block_size;
+                    let layout = Layout::from_size_align(block_size, block_align).unwrap();
+                    allocator.fallback_alloc(layout)
+                }
+            },
+            None => allocator.fallback_alloc(layout),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let mut allocator = self.lock();
+        match list_index(&layout) {
+            Some(index) => {
+                let new_node = ListNode {
+                    next: allocator.list_heads[index].take(),
+                };
+                // Every block size must be able to hold a `ListNode`.
+                assert!(mem::size_of::<ListNode>() <= BLOCK_SIZES[index]);
+                assert!(mem::align_of::<ListNode>() <= BLOCK_SIZES[index]);
+                let new_node_ptr = ptr as *mut ListNode;
+                unsafe {
+                    new_node_ptr.write(new_node);
+                    allocator.list_heads[index] = Some(&mut *new_node_ptr);
+                }
+            }
+            None => unsafe { allocator.fallback_allocator.deallocate(ptr, layout) },
+        }
+    }
+}
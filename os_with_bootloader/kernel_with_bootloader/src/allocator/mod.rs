@@ -0,0 +1,109 @@
+// Only the allocator in use is built; the fixed-size block allocator falls back
+// to the linked-list one.
+#[cfg(feature = "bump_allocator")]
+pub mod bump;
+#[cfg(not(any(feature = "bump_allocator", feature = "linked_list_allocator")))]
+pub mod fixed_size_block;
+#[cfg(not(feature = "bump_allocator"))]
+pub mod linked_list;
+
+use crate::memory::paging::{MemoryError, MemoryManager, Permissions};
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use x86_64::instructions::interrupts;
+use x86_64::VirtAddr;
+
+/// Virtual start address of the kernel heap.
+pub const HEAP_START: usize = 0x_4444_4444_0000;
+
+/// Size of the kernel heap.
+pub const HEAP_SIZE: usize = 32 * 1024 * 1024; // 32 MiB, enough for a console back buffer and scrollbacks
+
+/// The global allocator, chosen with the `bump_allocator` and
+/// `linked_list_allocator` features.
+#[cfg(feature = "bump_allocator")]
+type HeapAllocator = bump::BumpAllocator;
+#[cfg(all(feature = "linked_list_allocator", not(feature = "bump_allocator")))]
+type HeapAllocator = linked_list::LinkedListAllocator;
+#[cfg(not(any(feature = "bump_allocator", feature = "linked_list_allocator")))]
+type HeapAllocator = fixed_size_block::FixedSizeBlockAllocator;
+
+#[cfg_attr(not(test), global_allocator)]
+static ALLOCATOR: Locked<HeapAllocator> = Locked::new(HeapAllocator::new());
+
+/// Maps the heap pages and hands the region to the global allocator.
+pub fn init_heap(memory_manager: &mut MemoryManager) -> Result<(), MemoryError> {
+    memory_manager.map_range(
+        VirtAddr::new(HEAP_START as u64),
+        HEAP_SIZE as u64,
+        Permissions::KERNEL_DATA,
+    )?;
+
+    unsafe {
+        ALLOCATOR.lock().init(HEAP_START, HEAP_SIZE);
+    }
+
+    Ok(())
+}
+
+/// A wrapper around `spin::Mutex` so that `GlobalAlloc` can be implemented for it.
+///
+/// The lock is held with interrupts disabled, so an interrupt handler that
+/// allocates can never spin on a heap lock held by the code it interrupted.
+pub struct Locked<A> {
+    inner: spin::Mutex<A>,
+}
+
+impl<A> Locked<A> {
+    /// Wraps the given allocator.
+    pub const fn new(inner: A) -> Self {
+        Locked {
+            inner: spin::Mutex::new(inner),
+        }
+    }
+
+    /// Disables interrupts and locks the wrapped allocator until the guard is dropped.
+    pub fn lock(&self) -> LockedGuard<'_, A> {
+        let interrupts_enabled = interrupts::are_enabled();
+        interrupts::disable();
+        LockedGuard {
+            guard: ManuallyDrop::new(self.inner.lock()),
+            interrupts_enabled,
+        }
+    }
+}
+
+/// Access to an allocator locked by `Locked::lock`.
+pub struct LockedGuard<'a, A> {
+    guard: ManuallyDrop<spin::MutexGuard<'a, A>>,
+    interrupts_enabled: bool,
+}
+
+impl<A> Deref for LockedGuard<'_, A> {
+    type Target = A;
+
+    fn deref(&self) -> &A {
+        &self.guard
+    }
+}
+
+impl<A> DerefMut for LockedGuard<'_, A> {
+    fn deref_mut(&mut self) -> &mut A {
+        &mut self.guard
+    }
+}
+
+impl<A> Drop for LockedGuard<'_, A> {
+    fn drop(&mut self) {
+        // Unlock before interrupts come back on.
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        if self.interrupts_enabled {
+            interrupts::enable();
+        }
+    }
+}
+
+/// Aligns the given address upwards to `align`, which must be a power of two.
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
@@ -0,0 +1,92 @@
+use crate::memory::paging::{MemoryError, MemoryManager};
+use spin::Lazy;
+use x86_64::instructions::segmentation::{Segment, CS, DS, ES, SS};
+use x86_64::instructions::tables::load_tss;
+use x86_64::structures::gdt::{Descriptor, GlobalDescriptorTable, SegmentSelector};
+use x86_64::structures::tss::TaskStateSegment;
+use x86_64::VirtAddr;
+
+/// Interrupt stack table slot used by the double fault handler.
+pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
+
+/// Size of the dedicated double fault stack.
+const DOUBLE_FAULT_STACK_SIZE: usize = 4096 * 5;
+
+/// Virtual address of the unmapped guard page below the double fault stack.
+const DOUBLE_FAULT_GUARD_PAGE: u64 = 0x_5555_5555_0000;
+
+/// The task state segment; the CPU only reads it when switching stacks.
+static mut TSS: TaskStateSegment = TaskStateSegment::new();
+
+/// Double fault stack used until the memory manager maps a guarded one.
+static mut BOOT_DOUBLE_FAULT_STACK: [u8; DOUBLE_FAULT_STACK_SIZE] = [0; DOUBLE_FAULT_STACK_SIZE];
+
+/// The kernel's GDT together with the selectors of its entries.
+struct Gdt {
+    table: GlobalDescriptorTable,
+    selectors: Selectors,
+}
+
+struct Selectors {
+    code_selector: SegmentSelector,
+    data_selector: SegmentSelector,
+    tss_selector: SegmentSelector,
+}
+
+static GDT: Lazy<Gdt> = Lazy::new(|| {
+    let mut table = GlobalDescriptorTable::new();
+    let code_selector = table.add_entry(Descriptor::kernel_code_segment());
+    let data_selector = table.add_entry(Descriptor::kernel_data_segment());
+    // The TSS is a static, so the descriptor stays valid.
+    let tss_selector =
+        table.add_entry(unsafe { Descriptor::tss_segment_unchecked(&raw const TSS) });
+    Gdt {
+        table,
+        selectors: Selectors {
+            code_selector,
+            data_selector,
+            tss_selector,
+        },
+    }
+});
+
+/// Loads the kernel's GDT and TSS, replacing the segments set up by the bootloader.
+pub fn init() {
+    // Stacks grow downwards, so the table holds the top of the stack.
+    let boot_stack = VirtAddr::from_ptr(&raw const BOOT_DOUBLE_FAULT_STACK);
+    set_double_fault_stack(boot_stack + DOUBLE_FAULT_STACK_SIZE);
+
+    GDT.table.load();
+    let selectors = &GDT.selectors;
+    unsafe {
+        CS::set_reg(selectors.code_selector);
+        DS::set_reg(selectors.data_selector);
+        ES::set_reg(selectors.data_selector);
+        SS::set_reg(selectors.data_selector);
+        load_tss(selectors.tss_selector);
+    }
+}
+
+/// Moves the double fault handler onto a stack above an unmapped guard page, so
+/// overflowing it faults instead of overwriting the memory below.
+pub fn init_double_fault_stack(memory_manager: &mut MemoryManager) -> Result<(), MemoryError> {
+    let stack = memory_manager.allocate_stack(
+        VirtAddr::new(DOUBLE_FAULT_GUARD_PAGE),
+        DOUBLE_FAULT_STACK_SIZE as u64,
+    )?;
+    set_double_fault_stack(stack.top);
+    log::debug!(
+        "Double fault stack at {:#x}..{:#x}",
+        stack.bottom,
+        stack.top
+    );
+    Ok(())
+}
+
+/// Points the double fault entry of the interrupt stack table at a stack top.
+fn set_double_fault_stack(top: VirtAddr) {
+    // A single aligned store, so a double fault sees either the old or the new stack.
+    unsafe {
+        TSS.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = top;
+    }
+}
@@ -0,0 +1,331 @@
+use crate::graphics::image::{pixel_buffer, DecodeError, Image, BYTES_PER_PIXEL};
+
+/// The first bytes of every BMP file.
+pub const SIGNATURE: &[u8] = b"BM";
+
+/// Size of the file header in front of the info header.
+const FILE_HEADER_SIZE: usize = 14;
+
+/// Size of `BITMAPINFOHEADER`, the oldest info header supported.
+const INFO_HEADER_SIZE: usize = 40;
+
+/// Compression methods; everything else is run-length or embedded JPEG/PNG data.
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+/// Decodes an uncompressed BMP file with 1, 4, 8, 16, 24 or 32 bits per pixel.
+///
+/// BMP stores color channels in blue, green, red order; they are reordered to
+/// RGBA here.
+pub fn decode(data: &[u8]) -> Result<Image, DecodeError> {
+    if !data.starts_with(SIGNATURE) {
+        return Err(DecodeError::UnknownFormat);
+    }
+    let pixel_offset = read_u32(data, 10)? as usize;
+    let header_size = read_u32(data, FILE_HEADER_SIZE)? as usize;
+    if header_size < INFO_HEADER_SIZE {
+        return Err(DecodeError::Unsupported("OS/2 bitmap header"));
+    }
+    let width = read_u32(data, 18)? as i32;
+    let height = read_u32(data, 22)? as i32;
+    let bits_per_pixel = read_u16(data, 28)?;
+    let compression = read_u32(data, 30)?;
+    let colors_used = read_u32(data, 46)? as usize;
+    if width <= 0 || height == 0 {
+        return Err(DecodeError::InvalidHeader);
+    }
+    // Rows are stored bottom-up, unless the height is negative.
+    let (width, height, bottom_up) = (width as usize, height.unsigned_abs() as usize, height > 0);
+
+    let format = match (bits_per_pixel, compression) {
+        (1 | 4 | 8, BI_RGB) => {
+            let palette_start = FILE_HEADER_SIZE + header_size;
+            let entries = if colors_used == 0 {
+                1 << bits_per_pixel
+            } else {
+                colors_used
+            };
+            let palette_end = entries
+                .checked_mul(4)
+                .and_then(|size| size.checked_add(palette_start))
+                .ok_or(DecodeError::InvalidHeader)?;
+            let palette = data
+                .get(palette_start..palette_end)
+                .ok_or(DecodeError::Truncated)?;
+            PixelLayout::Indexed(palette)
+        }
+        (16, BI_RGB) => PixelLayout::Masked(ChannelMasks::new(0x7C00, 0x03E0, 0x001F, 0)),
+        (24, BI_RGB) => PixelLayout::Bgr,
+        // The fourth byte is unused rather than alpha, so the image is opaque.
+        (32, BI_RGB) => PixelLayout::Masked(ChannelMasks::new(0xFF_0000, 0xFF00, 0xFF, 0)),
+        (16 | 32, BI_BITFIELDS | BI_ALPHABITFIELDS) => {
+            // The masks follow the info header, or are part of the newer ones.
+            let has_alpha = compression == BI_ALPHABITFIELDS || header_size >= 56;
+            let alpha = if has_alpha { read_u32(data, 66)? } else { 0 };
+            PixelLayout::Masked(ChannelMasks::new(
+                read_u32(data, 54)?,
+                read_u32(data, 58)?,
+                read_u32(data, 62)?,
+                alpha,
+            ))
+        }
+        (_, BI_RGB | BI_BITFIELDS | BI_ALPHABITFIELDS) => {
+            return Err(DecodeError::Unsupported("bit depth"));
+        }
+        _ => return Err(DecodeError::Unsupported("compression")),
+    };
+
+    // Rows are padded to a multiple of four bytes.
+    let row_bytes = width
+        .checked_mul(bits_per_pixel as usize)
+        .map(|bits| bits.div_ceil(32) * 4)
+        .ok_or(DecodeError::InvalidHeader)?;
+    let end = row_bytes
+        .checked_mul(height)
+        .and_then(|size| size.checked_add(pixel_offset))
+        .ok_or(DecodeError::InvalidHeader)?;
+    let rows = data.get(pixel_offset..end).ok_or(DecodeError::Truncated)?;
+
+    let mut pixels = pixel_buffer(width, height)?;
+    for (index, row) in rows.chunks_exact(row_bytes).enumerate() {
+        let y = if bottom_up { height - 1 - index } else { index };
+        let out = &mut pixels[y * width * BYTES_PER_PIXEL..(y + 1) * width * BYTES_PER_PIXEL];
+        for (x, pixel) in out.chunks_exact_mut(BYTES_PER_PIXEL).enumerate() {
+            pixel.copy_from_slice(&format.pixel(row, x, bits_per_pixel)?);
+        }
+    }
+    Image::new(width, height, pixels).ok_or(DecodeError::InvalidHeader)
+}
+
+/// How the pixels of a row are stored.
+enum PixelLayout<'a> {
+    /// Indices into a palette of blue, green, red and reserved bytes.
+    Indexed(&'a [u8]),
+    /// Blue, green and red bytes.
+    Bgr,
+    /// Little-endian 16 or 32 bit values holding the channels under masks.
+    Masked(ChannelMasks),
+}
+
+impl PixelLayout<'_> {
+    /// The RGBA value of pixel `x` of a row.
+    fn pixel(&self, row: &[u8], x: usize, bits_per_pixel: u16) -> Result<[u8; 4], DecodeError> {
+        match self {
+            PixelLayout::Indexed(palette) => {
+                let bits = bits_per_pixel as usize;
+                let byte = row[x * bits / 8];
+                // The leftmost pixel is in the most significant bits.
+                let shift = 8 - bits - (x * bits % 8);
+                let index = (byte >> shift) as usize & ((1 << bits) - 1);
+                let entry = palette
+                    .get(index * 4..index * 4 + 3)
+                    .ok_or(DecodeError::InvalidHeader)?;
+                Ok([entry[2], entry[1], entry[0], 0xFF])
+            }
+            PixelLayout::Bgr => {
+                let bgr = &row[x * 3..x * 3 + 3];
+                Ok([bgr[2], bgr[1], bgr[0], 0xFF])
+            }
+            PixelLayout::Masked(masks) => {
+                let value = if bits_per_pixel == 16 {
+                    u16::from_le_bytes([row[x * 2], row[x * 2 + 1]]) as u32
+                } else {
+                    let bytes = &row[x * 4..x * 4 + 4];
+                    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+                };
+                Ok(masks.rgba(value))
+            }
+        }
+    }
+}
+
+/// The bits of a pixel value holding each channel.
+struct ChannelMasks {
+    red: u32,
+    green: u32,
+    blue: u32,
+    /// Zero if the image has no alpha channel.
+    alpha: u32,
+}
+
+impl ChannelMasks {
+    fn new(red: u32, green: u32, blue: u32, alpha: u32) -> Self {
+        ChannelMasks {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    fn rgba(&self, value: u32) -> [u8; 4] {
+        let alpha = if self.alpha == 0 {
+            0xFF
+        } else {
+            channel(value, self.alpha)
+        };
+        [
+            channel(value, self.red),
+            channel(value, self.green),
+            channel(value, self.blue),
+            alpha,
+        ]
+    }
+}
+
+/// Extracts the channel under `mask` and scales it to eight bits.
+fn channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let max = (mask >> mask.trailing_zeros()) as u64;
+    let raw = ((value & mask) >> mask.trailing_zeros()) as u64;
+    (raw * 0xFF / max) as u8
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, DecodeError> {
+    let bytes = data.get(offset..offset + 2).ok_or(DecodeError::Truncated)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, DecodeError> {
+    let bytes = data.get(offset..offset + 4).ok_or(DecodeError::Truncated)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// A BMP file with a `BITMAPINFOHEADER`, followed by `extra` (a palette or
+    /// channel masks) and the pixel rows.
+    fn bmp(
+        width: i32,
+        height: i32,
+        bits_per_pixel: u16,
+        compression: u32,
+        colors_used: u32,
+        extra: &[u8],
+        rows: &[u8],
+    ) -> Vec<u8> {
+        let pixel_offset = (FILE_HEADER_SIZE + INFO_HEADER_SIZE + extra.len()) as u32;
+        let mut data = Vec::from(SIGNATURE);
+        data.extend_from_slice(&(pixel_offset + rows.len() as u32).to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&pixel_offset.to_le_bytes());
+        data.extend_from_slice(&(INFO_HEADER_SIZE as u32).to_le_bytes());
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&bits_per_pixel.to_le_bytes());
+        data.extend_from_slice(&compression.to_le_bytes());
+        // Image size and resolution, which the decoder ignores.
+        data.extend_from_slice(&[0; 12]);
+        data.extend_from_slice(&colors_used.to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(extra);
+        data.extend_from_slice(rows);
+        data
+    }
+
+    #[test]
+    fn decodes_24_bit_bottom_up() {
+        #[rustfmt::skip]
+        let rows = [
+            // Bottom row: blue, white; each row is padded to eight bytes.
+            0xFF, 0x00, 0x00,  0xFF, 0xFF, 0xFF,  0, 0,
+            // Top row: red, green.
+            0x00, 0x00, 0xFF,  0x00, 0xFF, 0x00,  0, 0,
+        ];
+        let image = decode(&bmp(2, 2, 24, BI_RGB, 0, &[], &rows)).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 2));
+        assert_eq!(
+            image.pixels(),
+            [
+                [0xFF, 0x00, 0x00, 0xFF],
+                [0x00, 0xFF, 0x00, 0xFF],
+                [0x00, 0x00, 0xFF, 0xFF],
+                [0xFF, 0xFF, 0xFF, 0xFF],
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_8_bit_palette() {
+        // Blue, green, red and reserved bytes per entry.
+        #[rustfmt::skip]
+        let palette = [
+            0x10, 0x20, 0x30, 0,
+            0x40, 0x50, 0x60, 0,
+            0x70, 0x80, 0x90, 0,
+        ];
+        #[rustfmt::skip]
+        let rows = [
+            2, 1, 0, 0,
+            0, 2, 0, 0,
+        ];
+        let image = decode(&bmp(2, 2, 8, BI_RGB, 3, &palette, &rows)).unwrap();
+        assert_eq!(
+            image.pixels(),
+            [
+                [0x30, 0x20, 0x10, 0xFF],
+                [0x90, 0x80, 0x70, 0xFF],
+                [0x90, 0x80, 0x70, 0xFF],
+                [0x60, 0x50, 0x40, 0xFF],
+            ]
+        );
+    }
+
+    #[test]
+    fn palette_index_out_of_range_is_invalid() {
+        let palette = [0x10, 0x20, 0x30, 0];
+        let rows = [1, 0, 0, 0];
+        let data = bmp(1, 1, 8, BI_RGB, 1, &palette, &rows);
+        assert_eq!(decode(&data), Err(DecodeError::InvalidHeader));
+    }
+
+    #[test]
+    fn decodes_32_bit_bitfields_top_down() {
+        // Red, green and blue masks for pixels stored as R, G, B, X bytes.
+        let mut masks = Vec::new();
+        for mask in [0x0000_00FFu32, 0x0000_FF00, 0x00FF_0000] {
+            masks.extend_from_slice(&mask.to_le_bytes());
+        }
+        #[rustfmt::skip]
+        let rows = [
+            // A negative height stores the top row first.
+            0xFF, 0x00, 0x00, 0x00,  0x00, 0xFF, 0x00, 0x00,
+            0x00, 0x00, 0xFF, 0x00,  0x12, 0x34, 0x56, 0x78,
+        ];
+        let image = decode(&bmp(2, -2, 32, BI_BITFIELDS, 0, &masks, &rows)).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 2));
+        assert_eq!(
+            image.pixels(),
+            [
+                [0xFF, 0x00, 0x00, 0xFF],
+                [0x00, 0xFF, 0x00, 0xFF],
+                [0x00, 0x00, 0xFF, 0xFF],
+                [0x12, 0x34, 0x56, 0xFF],
+            ]
+        );
+    }
+
+    #[test]
+    fn scales_narrow_channels() {
+        // 5-5-5 pixels: full red, and half green.
+        let rows = [0x00, 0x7C, 0x00, 0x02];
+        let image = decode(&bmp(2, 1, 16, BI_RGB, 0, &[], &rows)).unwrap();
+        assert_eq!(image.pixels(), [[0xFF, 0, 0, 0xFF], [0, 0x83, 0, 0xFF]]);
+    }
+
+    #[test]
+    fn truncated_rows_are_reported() {
+        let rows = [0xFF, 0x00, 0x00, 0];
+        let mut data = bmp(1, 2, 24, BI_RGB, 0, &[], &rows);
+        assert_eq!(decode(&data), Err(DecodeError::Truncated));
+        data.truncate(20);
+        assert_eq!(decode(&data), Err(DecodeError::Truncated));
+    }
+}
@@ -0,0 +1,362 @@
+use crate::graphics::image::Image;
+use crate::writer::display::{Display, Region};
+use crate::writer::TextColor;
+
+/// A drawing surface over a region of the display.
+///
+/// Coordinates are relative to the top left of the region and may lie outside
+/// of it; everything is clipped to the region.
+pub struct Canvas<'a> {
+    display: &'a mut Display,
+    region: Region,
+}
+
+impl<'a> Canvas<'a> {
+    /// Creates a canvas over a region of the display, clipped to the display.
+    pub fn new(display: &'a mut Display, region: Region) -> Self {
+        let region = region.intersect(&display.region());
+        Canvas { display, region }
+    }
+
+    /// Retrieves the canvas width.
+    pub fn width(&self) -> usize {
+        self.region.width
+    }
+
+    /// Retrieves the canvas height.
+    pub fn height(&self) -> usize {
+        self.region.height
+    }
+
+    /// A canvas over part of this one, given relative to it and clipped to it.
+    pub fn sub_canvas(&mut self, region: Region) -> Canvas<'_> {
+        let region = Region::new(
+            self.region.x + region.x,
+            self.region.y + region.y,
+            region.width,
+            region.height,
+        )
+        .intersect(&self.region);
+        Canvas {
+            display: self.display,
+            region,
+        }
+    }
+
+    /// Fills the whole canvas with a color.
+    pub fn clear(&mut self, color: TextColor) {
+        self.display
+            .fill(self.region.xs(), self.region.ys(), color.rgb());
+    }
+
+    /// Sets a single pixel.
+    pub fn set_pixel(&mut self, x: isize, y: isize, color: TextColor) {
+        if let Some((x, y)) = self.to_display(x, y) {
+            self.display.write_pixel(x, y, color.rgb());
+        }
+    }
+
+    /// Fills a rectangle with a color.
+    pub fn fill_rect(&mut self, x: isize, y: isize, width: usize, height: usize, color: TextColor) {
+        let xs = clip(x, width, self.region.width);
+        let ys = clip(y, height, self.region.height);
+        self.display.fill(
+            self.region.x + xs.0..self.region.x + xs.1,
+            self.region.y + ys.0..self.region.y + ys.1,
+            color.rgb(),
+        );
+    }
+
+    /// Draws the one pixel wide outline of a rectangle.
+    pub fn draw_rect(&mut self, x: isize, y: isize, width: usize, height: usize, color: TextColor) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let (right, bottom) = (x + width as isize - 1, y + height as isize - 1);
+        self.fill_rect(x, y, width, 1, color);
+        self.fill_rect(x, bottom, width, 1, color);
+        self.fill_rect(x, y, 1, height, color);
+        self.fill_rect(right, y, 1, height, color);
+    }
+
+    /// Draws an image scaled to `width` by `height` pixels, like `blit`.
+    ///
+    /// Each pixel takes the color of the nearest image pixel, which keeps
+    /// edges sharp when scaling up by whole multiples.
+    pub fn blit_scaled(&mut self, x: isize, y: isize, width: usize, height: usize, image: &Image) {
+        let xs = clip(x, width, self.region.width);
+        let ys = clip(y, height, self.region.height);
+        for canvas_y in ys.0..ys.1 {
+            let image_y = (canvas_y as isize - y) as usize * image.height() / height;
+            for canvas_x in xs.0..xs.1 {
+                let image_x = (canvas_x as isize - x) as usize * image.width() / width;
+                let Some([r, g, b, alpha]) = image.pixel(image_x, image_y) else {
+                    continue;
+                };
+                let (display_x, display_y) = (self.region.x + canvas_x, self.region.y + canvas_y);
+                let color = match alpha {
+                    0 => continue,
+                    0xFF => (r, g, b),
+                    _ => {
+                        let (br, bg, bb) = self.display.read_pixel(display_x, display_y);
+                        TextColor::Rgb(r, g, b).blend(TextColor::Rgb(br, bg, bb), alpha)
+                    }
+                };
+                self.display.write_pixel(display_x, display_y, color);
+            }
+        }
+    }
+
+    /// The display position of a point on the canvas, if it is on the canvas.
+    fn to_display(&self, x: isize, y: isize) -> Option<(usize, usize)> {
+        let (x, y) = (usize::try_from(x).ok()?, usize::try_from(y).ok()?);
+        (x < self.region.width && y < self.region.height)
+            .then(|| (self.region.x + x, self.region.y + y))
+    }
+}
+
+// Lines, circles and unscaled images are not drawn by the kernel yet; see the tests.
+#[allow(dead_code)]
+impl Canvas<'_> {
+    /// Draws a line between two points, both included, with Bresenham's algorithm.
+    pub fn draw_line(&mut self, x0: isize, y0: isize, x1: isize, y1: isize, color: TextColor) {
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (step_x, step_y) = ((x1 - x0).signum(), (y1 - y0).signum());
+        let mut error = dx + dy;
+        let (mut x, mut y) = (x0, y0);
+        loop {
+            self.set_pixel(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Draws the outline of a circle with the midpoint algorithm.
+    pub fn draw_circle(
+        &mut self,
+        center_x: isize,
+        center_y: isize,
+        radius: usize,
+        color: TextColor,
+    ) {
+        for_circle_octants(radius, |x, y| {
+            for (dx, dy) in [(x, y), (y, x), (-y, x), (-x, y)] {
+                self.set_pixel(center_x + dx, center_y + dy, color);
+                self.set_pixel(center_x - dx, center_y - dy, color);
+            }
+        });
+    }
+
+    /// Fills a circle.
+    pub fn fill_circle(
+        &mut self,
+        center_x: isize,
+        center_y: isize,
+        radius: usize,
+        color: TextColor,
+    ) {
+        for_circle_octants(radius, |x, y| {
+            // Each step covers four horizontal spans, mirrored around the center.
+            for (half_width, dy) in [(x, y), (x, -y), (y, x), (y, -x)] {
+                let width = 2 * half_width as usize + 1;
+                self.fill_rect(center_x - half_width, center_y + dy, width, 1, color);
+            }
+        });
+    }
+
+    /// Draws an image with its top left corner at the given position, blending
+    /// it over the canvas by its alpha channel.
+    pub fn blit(&mut self, x: isize, y: isize, image: &Image) {
+        self.blit_scaled(x, y, image.width(), image.height(), image);
+    }
+}
+
+/// The part of the span of `length` pixels from `start` that lies within
+/// `0..limit`, as a start and end.
+fn clip(start: isize, length: usize, limit: usize) -> (usize, usize) {
+    let end = start
+        .saturating_add_unsigned(length)
+        .clamp(0, limit as isize);
+    let start = start.clamp(0, end);
+    (start as usize, end as usize)
+}
+
+/// Walks the first octant of a circle, calling `f` with each point relative to
+/// the center; the other octants follow by symmetry.
+fn for_circle_octants(radius: usize, mut f: impl FnMut(isize, isize)) {
+    let (mut x, mut y) = (radius as isize, 0);
+    let mut error = 1 - x;
+    while x >= y {
+        f(x, y);
+        y += 1;
+        if error < 0 {
+            error += 2 * y + 1;
+        } else {
+            x -= 1;
+            error += 2 * (y - x) + 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use bootloader_api::info::PixelFormat;
+
+    const WIDTH: usize = 16;
+    const HEIGHT: usize = 12;
+    const INK: TextColor = TextColor::Rgb(0xFF, 0x80, 0x40);
+
+    /// The whole display.
+    const FULL: Region = Region::new(0, 0, WIDTH, HEIGHT);
+
+    fn test_display() -> Display {
+        Display::for_test(PixelFormat::Rgb, 4, WIDTH, HEIGHT, WIDTH)
+    }
+
+    /// The display positions of all pixels drawn in `INK`.
+    fn inked(display: &mut Display) -> Vec<(usize, usize)> {
+        let mut inked = Vec::new();
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                if display.read_pixel(x, y) == INK.rgb() {
+                    inked.push((x, y));
+                }
+            }
+        }
+        inked
+    }
+
+    #[test]
+    fn clip_keeps_the_part_within_the_limit() {
+        assert_eq!(clip(2, 3, 10), (2, 5));
+        assert_eq!(clip(-3, 5, 10), (0, 2));
+        assert_eq!(clip(8, 5, 10), (8, 10));
+        assert_eq!(clip(12, 3, 10), (10, 10));
+        assert_eq!(clip(-10, 3, 10), (0, 0));
+        assert_eq!(clip(isize::MAX - 1, usize::MAX, 10), (10, 10));
+    }
+
+    #[test]
+    fn circle_octant_runs_from_the_axis_to_the_diagonal() {
+        let mut points = Vec::new();
+        for_circle_octants(0, |x, y| points.push((x, y)));
+        assert_eq!(points, [(0, 0)]);
+
+        for radius in 1..20 {
+            let mut points = Vec::new();
+            for_circle_octants(radius, |x, y| points.push((x, y)));
+            let r = radius as isize;
+            assert_eq!(points[0], (r, 0));
+            let &(last_x, last_y) = points.last().unwrap();
+            assert!(last_x - last_y <= 1, "radius {}", radius);
+            for (index, &(x, y)) in points.iter().enumerate() {
+                assert_eq!(y, index as isize);
+                assert!((x * x + y * y - r * r).abs() <= r, "radius {}", radius);
+            }
+        }
+    }
+
+    #[test]
+    fn lines_include_both_endpoints() {
+        let ends = [(8, 6), (15, 6), (15, 11), (13, 0), (0, 1), (2, 11), (8, 6)];
+        for &(x1, y1) in &ends {
+            let mut display = test_display();
+            Canvas::new(&mut display, FULL).draw_line(8, 6, x1, y1, INK);
+            let inked = inked(&mut display);
+            assert!(inked.contains(&(8, 6)));
+            assert!(inked.contains(&(x1 as usize, y1 as usize)));
+            let steps = (x1 - 8).abs().max((y1 - 6).abs()) as usize;
+            assert_eq!(inked.len(), steps + 1, "line to {:?}", (x1, y1));
+        }
+    }
+
+    #[test]
+    fn lines_are_clipped_to_the_canvas() {
+        let mut display = test_display();
+        Canvas::new(&mut display, Region::new(4, 4, 4, 4)).draw_line(-5, 1, 20, 1, INK);
+        assert_eq!(inked(&mut display), [(4, 5), (5, 5), (6, 5), (7, 5)]);
+    }
+
+    #[test]
+    fn rect_outline_and_sub_canvas_are_clipped() {
+        let mut display = test_display();
+        let mut canvas = Canvas::new(&mut display, Region::new(2, 2, 6, 5));
+        canvas.draw_rect(0, 0, 4, 3, INK);
+        let mut sub = canvas.sub_canvas(Region::new(5, 4, 10, 10));
+        assert_eq!((sub.width(), sub.height()), (1, 1));
+        sub.fill_rect(-1, -1, 3, 3, INK);
+        assert_eq!(
+            inked(&mut display),
+            [
+                (2, 2),
+                (3, 2),
+                (4, 2),
+                (5, 2),
+                (2, 3),
+                (5, 3),
+                (2, 4),
+                (3, 4),
+                (4, 4),
+                (5, 4),
+                (7, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn circles_are_symmetric() {
+        let mut display = test_display();
+        Canvas::new(&mut display, FULL).draw_circle(7, 5, 4, INK);
+        let outline = inked(&mut display);
+        for &(x, y) in &outline {
+            let (dx, dy) = (x as isize - 7, y as isize - 5);
+            for (mx, my) in [(-dx, dy), (dx, -dy), (dy, dx)] {
+                assert!(outline.contains(&((7 + mx) as usize, (5 + my) as usize)));
+            }
+        }
+        assert!(outline.contains(&(11, 5)) && outline.contains(&(7, 1)));
+
+        let mut display = test_display();
+        Canvas::new(&mut display, FULL).fill_circle(7, 5, 4, INK);
+        let filled = inked(&mut display);
+        assert!(outline.iter().all(|point| filled.contains(point)));
+        assert!(filled.contains(&(7, 5)) && !filled.contains(&(11, 9)));
+    }
+
+    #[test]
+    fn blit_blends_by_alpha() {
+        let mut display = test_display();
+        display.fill(0..WIDTH, 0..HEIGHT, (0, 0, 100));
+        #[rustfmt::skip]
+        let pixels = vec![
+            0xFF, 0x80, 0x40, 0xFF,  10, 20, 30, 0,
+            0xFF, 0xFF, 0xFF, 0x80,  0, 0, 0, 0xFF,
+        ];
+        let image = Image::new(2, 2, pixels).unwrap();
+        Canvas::new(&mut display, FULL).blit(-1, 10, &image);
+        // Only the right column is on the canvas, and its top pixel is transparent.
+        assert_eq!(display.read_pixel(0, 10), (0, 0, 100));
+        assert_eq!(display.read_pixel(0, 11), (0, 0, 0));
+
+        let mut display = test_display();
+        display.fill(0..WIDTH, 0..HEIGHT, (0, 0, 100));
+        Canvas::new(&mut display, FULL).blit(3, 3, &image);
+        assert_eq!(display.read_pixel(3, 3), INK.rgb());
+        assert_eq!(display.read_pixel(4, 3), (0, 0, 100));
+        assert_eq!(display.read_pixel(3, 4), (0x80, 0x80, 0xB2));
+        assert_eq!(display.read_pixel(4, 4), (0, 0, 0));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::graphics::{bmp, qoi};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Bytes per pixel of an image: red, green, blue and alpha.
+pub const BYTES_PER_PIXEL: usize = 4;
+
+/// An image of RGBA pixels, stored row by row from the top left.
+///
+/// Pixels are kept in this order whatever the file format stored; the display
+/// converts them to its own pixel format when they are drawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+/// Reasons an image file cannot be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The data is not in a known image format.
+    UnknownFormat,
+    /// The data ends before the image does.
+    Truncated,
+    /// The header is inconsistent, e.g. gives a size of zero.
+    InvalidHeader,
+    /// The image uses a feature the decoder does not support.
+    Unsupported(&'static str),
+    /// The kernel heap cannot hold the decoded pixels.
+    OutOfMemory,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::UnknownFormat => write!(f, "unknown image format"),
+            DecodeError::Truncated => write!(f, "image data is truncated"),
+            DecodeError::InvalidHeader => write!(f, "invalid image header"),
+            DecodeError::Unsupported(feature) => {
+                write!(f, "unsupported image feature: {}", feature)
+            }
+            DecodeError::OutOfMemory => write!(f, "not enough memory for the image"),
+        }
+    }
+}
+
+impl Image {
+    /// Wraps RGBA pixel data, which must hold exactly `width * height` pixels.
+    pub fn new(width: usize, height: usize, pixels: Vec<u8>) -> Option<Self> {
+        let size = width.checked_mul(height)?.checked_mul(BYTES_PER_PIXEL)?;
+        (pixels.len() == size).then_some(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Decodes a BMP or QOI file, telling the format apart by its signature.
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        if data.starts_with(bmp::SIGNATURE) {
+            bmp::decode(data)
+        } else if data.starts_with(qoi::SIGNATURE) {
+            qoi::decode(data)
+        } else {
+            Err(DecodeError::UnknownFormat)
+        }
+    }
+
+    /// Retrieves the image width.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Retrieves the image height.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The RGBA value of a pixel, if it is in the image.
+    pub fn pixel(&self, x: usize, y: usize) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let offset = (y * self.width + x) * BYTES_PER_PIXEL;
+        let mut pixel = [0; BYTES_PER_PIXEL];
+        pixel.copy_from_slice(&self.pixels[offset..offset + BYTES_PER_PIXEL]);
+        Some(pixel)
+    }
+}
+
+#[cfg(test)]
+impl Image {
+    /// The RGBA values of all pixels, row by row from the top left.
+    pub fn pixels(&self) -> Vec<[u8; 4]> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.pixel(x, y).unwrap())
+            .collect()
+    }
+}
+
+/// Allocates zeroed pixel data for an image of the given size, for decoders.
+///
+/// The size comes from untrusted headers, so running out of heap is reported
+/// rather than fatal.
+pub(super) fn pixel_buffer(width: usize, height: usize) -> Result<Vec<u8>, DecodeError> {
+    let size = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(BYTES_PER_PIXEL))
+        .ok_or(DecodeError::InvalidHeader)?;
+    let mut pixels = Vec::new();
+    pixels
+        .try_reserve_exact(size)
+        .map_err(|_| DecodeError::OutOfMemory)?;
+    pixels.resize(size, 0);
+    Ok(pixels)
+}
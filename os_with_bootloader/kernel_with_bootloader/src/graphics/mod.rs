@@ -0,0 +1,8 @@
+pub mod bmp;
+pub mod canvas;
+pub mod image;
+pub mod qoi;
+pub mod splash;
+
+pub use canvas::Canvas;
+pub use image::{DecodeError, Image};
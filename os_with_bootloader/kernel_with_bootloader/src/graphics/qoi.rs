@@ -0,0 +1,228 @@
+use crate::graphics::image::{pixel_buffer, DecodeError, Image, BYTES_PER_PIXEL};
+
+/// The first bytes of every QOI file.
+pub const SIGNATURE: &[u8] = b"qoif";
+
+/// Size of the header: signature, width, height, channels and color space.
+const HEADER_SIZE: usize = 14;
+
+/// Chunk tags; the 2 bit tags are in the top bits of the first byte.
+const OP_RGB: u8 = 0xFE;
+const OP_RGBA: u8 = 0xFF;
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const TAG_MASK: u8 = 0xC0;
+
+/// Decodes a QOI ("Quite OK Image") file.
+///
+/// Both color spaces are decoded the same way, as the display does not tell
+/// them apart either.
+pub fn decode(data: &[u8]) -> Result<Image, DecodeError> {
+    if !data.starts_with(SIGNATURE) {
+        return Err(DecodeError::UnknownFormat);
+    }
+    let header = data.get(..HEADER_SIZE).ok_or(DecodeError::Truncated)?;
+    let width = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let height = u32::from_be_bytes([header[8], header[9], header[10], header[11]]) as usize;
+    let channels = header[12];
+    if width == 0 || height == 0 || !(3..=4).contains(&channels) {
+        return Err(DecodeError::InvalidHeader);
+    }
+
+    let mut pixels = pixel_buffer(width, height)?;
+    let mut chunks = data[HEADER_SIZE..].iter().copied();
+    let mut next = || chunks.next().ok_or(DecodeError::Truncated);
+    // Previously seen pixels, by hash.
+    let mut seen = [[0u8; 4]; 64];
+    let mut pixel = [0, 0, 0, 0xFF];
+    let mut run = 0;
+    for out in pixels.chunks_exact_mut(BYTES_PER_PIXEL) {
+        if run > 0 {
+            run -= 1;
+        } else {
+            let tag = next()?;
+            match tag {
+                OP_RGB => pixel = [next()?, next()?, next()?, pixel[3]],
+                OP_RGBA => pixel = [next()?, next()?, next()?, next()?],
+                _ => match tag & TAG_MASK {
+                    OP_INDEX => pixel = seen[(tag & 0x3F) as usize],
+                    OP_DIFF => {
+                        // Each channel differs by -2 to 1 from the previous pixel.
+                        let diff = |shift: u8| ((tag >> shift) & 0x03).wrapping_sub(2);
+                        pixel[0] = pixel[0].wrapping_add(diff(4));
+                        pixel[1] = pixel[1].wrapping_add(diff(2));
+                        pixel[2] = pixel[2].wrapping_add(diff(0));
+                    }
+                    OP_LUMA => {
+                        // Red and blue differ relative to the difference of green.
+                        let green = (tag & 0x3F).wrapping_sub(32);
+                        let byte = next()?;
+                        let red = green.wrapping_add(byte >> 4).wrapping_sub(8);
+                        let blue = green.wrapping_add(byte & 0x0F).wrapping_sub(8);
+                        pixel[0] = pixel[0].wrapping_add(red);
+                        pixel[1] = pixel[1].wrapping_add(green);
+                        pixel[2] = pixel[2].wrapping_add(blue);
+                    }
+                    // The remaining tag is a run of the previous pixel, 1 to 62 times.
+                    _ => run = tag & 0x3F,
+                },
+            }
+            seen[hash(pixel)] = pixel;
+        }
+        out.copy_from_slice(&pixel);
+    }
+    Image::new(width, height, pixels).ok_or(DecodeError::InvalidHeader)
+}
+
+/// Position of a pixel in the table of previously seen pixels.
+fn hash([r, g, b, a]: [u8; 4]) -> usize {
+    (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// Marks the end of a QOI stream.
+    const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+    fn header(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::from(SIGNATURE);
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        // Four channels, sRGB.
+        data.extend_from_slice(&[4, 0]);
+        data
+    }
+
+    /// Encodes pixels with every chunk type the format has.
+    fn encode(width: u32, height: u32, pixels: &[[u8; 4]]) -> Vec<u8> {
+        let mut data = header(width, height);
+        let mut seen = [[0u8; 4]; 64];
+        let mut previous = [0, 0, 0, 0xFF];
+        let mut run = 0u8;
+        for (index, &pixel) in pixels.iter().enumerate() {
+            if pixel == previous {
+                run += 1;
+                if run == 62 || index == pixels.len() - 1 {
+                    data.push(TAG_MASK | (run - 1));
+                    run = 0;
+                }
+            } else {
+                if run > 0 {
+                    data.push(TAG_MASK | (run - 1));
+                    run = 0;
+                }
+                let diff = |channel: usize| pixel[channel].wrapping_sub(previous[channel]) as i8;
+                let (red, green, blue) = (diff(0), diff(1), diff(2));
+                let (red_green, blue_green) = (red.wrapping_sub(green), blue.wrapping_sub(green));
+                if seen[hash(pixel)] == pixel {
+                    data.push(OP_INDEX | hash(pixel) as u8);
+                } else if pixel[3] != previous[3] {
+                    data.extend_from_slice(&[OP_RGBA, pixel[0], pixel[1], pixel[2], pixel[3]]);
+                } else if [red, green, blue].iter().all(|diff| (-2..=1).contains(diff)) {
+                    let bias = |diff: i8| (diff + 2) as u8;
+                    data.push(OP_DIFF | bias(red) << 4 | bias(green) << 2 | bias(blue));
+                } else if (-32..=31).contains(&green)
+                    && (-8..=7).contains(&red_green)
+                    && (-8..=7).contains(&blue_green)
+                {
+                    data.push(OP_LUMA | (green + 32) as u8);
+                    data.push(((red_green + 8) as u8) << 4 | (blue_green + 8) as u8);
+                } else {
+                    data.extend_from_slice(&[OP_RGB, pixel[0], pixel[1], pixel[2]]);
+                }
+            }
+            seen[hash(pixel)] = pixel;
+            previous = pixel;
+        }
+        data.extend_from_slice(&END_MARKER);
+        data
+    }
+
+    #[test]
+    fn decodes_each_chunk_type() {
+        let mut data = header(7, 1);
+        #[rustfmt::skip]
+        data.extend_from_slice(&[
+            OP_RGB, 10, 20, 30,
+            // Red +1, green -2, blue 0.
+            OP_DIFF | 3 << 4 | 2,
+            // Green +5, red green -3, blue green +2.
+            OP_LUMA | 37, 5 << 4 | 10,
+            // Two more of the same.
+            TAG_MASK | 1,
+            // Back to the first pixel.
+            OP_INDEX | hash([10, 20, 30, 0xFF]) as u8,
+            OP_RGBA, 1, 2, 3, 4,
+        ]);
+        data.extend_from_slice(&END_MARKER);
+        let image = decode(&data).unwrap();
+        assert_eq!(
+            image.pixels(),
+            [
+                [10, 20, 30, 0xFF],
+                [11, 18, 30, 0xFF],
+                [13, 23, 37, 0xFF],
+                [13, 23, 37, 0xFF],
+                [13, 23, 37, 0xFF],
+                [10, 20, 30, 0xFF],
+                [1, 2, 3, 4],
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips_through_the_encoder() {
+        let mut pixels: Vec<[u8; 4]> = Vec::new();
+        // Runs longer than a single chunk can hold.
+        pixels.extend([[0, 0, 0, 0xFF]; 70]);
+        // Small and medium steps, wrapping around.
+        for step in 0..40u8 {
+            let last = *pixels.last().unwrap();
+            let delta = if step % 2 == 0 { 1 } else { 0xFE };
+            pixels.push([last[0].wrapping_add(delta), last[1], last[2].wrapping_sub(1), 0xFF]);
+            pixels.push([last[0].wrapping_add(9), last[1].wrapping_add(20), last[2], 0xFF]);
+        }
+        // Noise, with changing alpha and repeated colors.
+        let mut state = 0x1234_5678u32;
+        while pixels.len() < 16 * 16 {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let [r, g, b, a] = state.to_le_bytes();
+            let pixel = match a % 4 {
+                0 => pixels[pixels.len() - 1 - (b as usize % 16)],
+                1 => [r, g, b, a],
+                _ => [r, g, b, 0xFF],
+            };
+            pixels.push(pixel);
+        }
+        let data = encode(16, 16, &pixels);
+        let image = decode(&data).unwrap();
+        assert_eq!((image.width(), image.height()), (16, 16));
+        assert_eq!(image.pixels(), pixels);
+    }
+
+    #[test]
+    fn logo_round_trips() {
+        let logo = decode(include_bytes!("../../assets/logo.qoi")).unwrap();
+        let pixels = logo.pixels();
+        let data = encode(logo.width() as u32, logo.height() as u32, &pixels);
+        assert_eq!(decode(&data), Ok(logo));
+    }
+
+    #[test]
+    fn truncated_data_is_reported() {
+        let data = encode(2, 2, &[[1, 2, 3, 4]; 4]);
+        assert_eq!(decode(&data[..HEADER_SIZE]), Err(DecodeError::Truncated));
+        assert_eq!(decode(&data[..10]), Err(DecodeError::Truncated));
+    }
+
+    #[test]
+    fn channel_count_other_than_three_or_four_is_invalid() {
+        let mut two_channels = header(1, 1);
+        two_channels[12] = 2;
+        assert_eq!(decode(&two_channels), Err(DecodeError::InvalidHeader));
+    }
+}
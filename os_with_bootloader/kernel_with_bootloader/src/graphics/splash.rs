@@ -0,0 +1,92 @@
+use crate::graphics::{DecodeError, Image};
+use crate::timer;
+use crate::writer::{self, Console, Region, TextColor};
+use x86_64::instructions::hlt;
+
+/// The logo shown while the kernel boots, in any format `Image::decode` reads.
+static LOGO: &[u8] = include_bytes!("../../assets/logo.qoi");
+
+/// How long the splash stays on screen before the console takes over.
+pub const SPLASH_DURATION_MS: u64 = 1500;
+
+/// Height of the progress bar below the logo, including its frame.
+const PROGRESS_BAR_HEIGHT: usize = 10;
+
+/// Space between the logo and the progress bar.
+const PROGRESS_BAR_GAP: usize = 16;
+
+/// Shows the boot logo centered on the display for `SPLASH_DURATION_MS`, with
+/// a progress bar counting down the time, then hands the screen back to the
+/// console on screen.
+///
+/// Needs the kernel heap for the decoded logo and the timer interrupt for the
+/// delay. Without a framebuffer this does nothing.
+pub fn show() -> Result<(), DecodeError> {
+    let logo = Image::decode(LOGO)?;
+    let progress_bar = writer::with_console(|console| {
+        // The cursor would otherwise keep blinking in the corner.
+        console.active_terminal().set_cursor_visible(false);
+        draw(console, &logo)
+    });
+    let Some(progress_bar) = progress_bar else {
+        return Ok(());
+    };
+    let start = timer::uptime_ms();
+    loop {
+        let elapsed = timer::uptime_ms() - start;
+        writer::with_console(|console| draw_progress(console, progress_bar, elapsed));
+        if elapsed >= SPLASH_DURATION_MS {
+            break;
+        }
+        hlt();
+    }
+    writer::with_console(|console| {
+        let terminal = console.active_terminal();
+        terminal.set_cursor_visible(true);
+        terminal.redraw();
+    });
+    Ok(())
+}
+
+/// Draws the logo centered on a cleared display, taking up at most half of its
+/// width and height, and returns the region of the progress bar below it.
+fn draw(console: &mut Console, logo: &Image) -> Region {
+    let region = console.region();
+    let (width, height) = fit(
+        logo.width(),
+        logo.height(),
+        region.width / 2,
+        region.height / 2,
+    );
+    let (x, y) = ((region.width - width) / 2, (region.height - height) / 2);
+    let mut canvas = console.canvas(region);
+    canvas.clear(TextColor::DEFAULT_BACKGROUND);
+    canvas.blit_scaled(x as isize, y as isize, width, height, logo);
+    Region::new(x, y + height + PROGRESS_BAR_GAP, width, PROGRESS_BAR_HEIGHT)
+}
+
+/// Draws the frame of the progress bar, filled by the share of
+/// `SPLASH_DURATION_MS` that has `elapsed`.
+fn draw_progress(console: &mut Console, region: Region, elapsed: u64) {
+    let mut canvas = console.canvas(region);
+    let (width, height) = (canvas.width(), canvas.height());
+    canvas.draw_rect(0, 0, width, height, TextColor::Gray);
+    // Leave a pixel of space between the frame and the bar.
+    let inner = Region::new(2, 2, width.saturating_sub(4), height.saturating_sub(4));
+    let mut bar = canvas.sub_canvas(inner);
+    let filled = elapsed.min(SPLASH_DURATION_MS) * bar.width() as u64 / SPLASH_DURATION_MS;
+    bar.fill_rect(0, 0, filled as usize, bar.height(), TextColor::White);
+}
+
+/// The size to draw an image at within a box: scaled up by a whole multiple if
+/// it is smaller, or scaled down keeping its aspect ratio if it is larger.
+fn fit(width: usize, height: usize, max_width: usize, max_height: usize) -> (usize, usize) {
+    if width <= max_width && height <= max_height {
+        let scale = (max_width / width).min(max_height / height);
+        (width * scale, height * scale)
+    } else if width * max_height > height * max_width {
+        (max_width, height * max_width / width)
+    } else {
+        (width * max_height / height, max_height)
+    }
+}
@@ -0,0 +1,171 @@
+use crate::serial::SERIAL1;
+use crate::writer::{self, TextColor, CONSOLE};
+use crate::{gdt, keyboard, timer};
+use core::fmt::{self, Write};
+use pic8259::ChainedPics;
+use spin::Lazy;
+use x86_64::instructions::port::Port;
+use x86_64::registers::control::Cr2;
+use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode};
+
+/// Vector of the first interrupt line of the primary PIC.
+pub const PIC_1_OFFSET: u8 = 32;
+
+/// Vector of the first interrupt line of the secondary PIC.
+pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
+
+/// The legacy 8259 PICs, remapped past the CPU exception vectors.
+pub static PICS: spin::Mutex<ChainedPics> =
+    spin::Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
+
+/// Vectors of the hardware interrupts handled by the kernel.
+#[derive(Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum InterruptIndex {
+    Timer = PIC_1_OFFSET,
+    Keyboard,
+}
+
+impl InterruptIndex {
+    fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    fn as_usize(self) -> usize {
+        usize::from(self.as_u8())
+    }
+}
+
+static IDT: Lazy<InterruptDescriptorTable> = Lazy::new(|| {
+    let mut idt = InterruptDescriptorTable::new();
+    idt.breakpoint.set_handler_fn(breakpoint_handler);
+    idt.invalid_opcode.set_handler_fn(invalid_opcode_handler);
+    idt.general_protection_fault.set_handler_fn(general_protection_fault_handler);
+    idt.page_fault.set_handler_fn(page_fault_handler);
+    unsafe {
+        idt.double_fault
+            .set_handler_fn(double_fault_handler)
+            .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
+    }
+    idt[InterruptIndex::Timer.as_usize()].set_handler_fn(timer_interrupt_handler);
+    idt[InterruptIndex::Keyboard.as_usize()].set_handler_fn(keyboard_interrupt_handler);
+    idt
+});
+
+/// Loads the interrupt descriptor table.
+pub fn init_idt() {
+    IDT.load();
+}
+
+/// Remaps the PICs and unmasks the interrupt lines the kernel handles.
+pub fn init_pics() {
+    let mut pics = PICS.lock();
+    unsafe {
+        pics.initialize();
+        // Unmask IRQ0 (timer), IRQ1 (keyboard) and IRQ2 (cascade to the secondary PIC).
+        pics.write_masks(0b1111_1000, 0b1111_1111);
+    }
+}
+
+/// Prints an exception report in red, restoring the previous color afterwards.
+///
+/// Fatal exceptions never return to the code they interrupted, so a console lock
+/// held there is forcibly released instead of deadlocking the report. Other
+/// exceptions resume that code, so while it holds the console, or before there
+/// is one, the report goes to serial instead.
+fn report(fatal: bool, write_report: impl FnOnce(&mut dyn Write) -> fmt::Result) {
+    if fatal && CONSOLE.is_locked() {
+        unsafe { CONSOLE.force_unlock() };
+    }
+    if let Some(mut console) = CONSOLE.try_lock()
+        && let Some(console) = console.as_mut()
+    {
+        let writer = console.active_terminal();
+        let previous_color = writer.color();
+        writer.set_color(TextColor::Red);
+        let _ = write_report(writer);
+        writer.set_color(previous_color);
+        console.flush();
+    } else if let Some(mut serial) = SERIAL1.try_lock() {
+        let _ = write_report(&mut *serial);
+    }
+}
+
+extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
+    report(false, |writer| {
+        writeln!(writer)?;
+        writeln!(writer, "EXCEPTION: BREAKPOINT")?;
+        writeln!(writer, "{:#?}", stack_frame)
+    });
+}
+
+extern "x86-interrupt" fn invalid_opcode_handler(stack_frame: InterruptStackFrame) {
+    report(true, |writer| {
+        writeln!(writer)?;
+        writeln!(writer, "EXCEPTION: INVALID OPCODE")?;
+        writeln!(writer, "{:#?}", stack_frame)
+    });
+    crate::hlt_loop();
+}
+
+extern "x86-interrupt" fn general_protection_fault_handler(
+    stack_frame: InterruptStackFrame,
+    error_code: u64,
+) {
+    report(true, |writer| {
+        writeln!(writer)?;
+        writeln!(writer, "EXCEPTION: GENERAL PROTECTION FAULT")?;
+        writeln!(writer, "Error Code: {:#x}", error_code)?;
+        writeln!(writer, "{:#?}", stack_frame)
+    });
+    crate::hlt_loop();
+}
+
+extern "x86-interrupt" fn page_fault_handler(
+    stack_frame: InterruptStackFrame,
+    error_code: PageFaultErrorCode,
+) {
+    report(true, |writer| {
+        writeln!(writer)?;
+        writeln!(writer, "EXCEPTION: PAGE FAULT")?;
+        writeln!(writer, "Accessed Address (CR2): {:?}", Cr2::read())?;
+        writeln!(writer, "Error Code: {:?}", error_code)?;
+        writeln!(writer, "{:#?}", stack_frame)
+    });
+    crate::hlt_loop();
+}
+
+extern "x86-interrupt" fn double_fault_handler(
+    stack_frame: InterruptStackFrame,
+    error_code: u64,
+) -> ! {
+    report(true, |writer| {
+        writeln!(writer)?;
+        writeln!(writer, "EXCEPTION: DOUBLE FAULT")?;
+        writeln!(writer, "Error Code: {:#x}", error_code)?;
+        writeln!(writer, "(a kernel stack overflow ends up here)")?;
+        writeln!(writer, "{:#?}", stack_frame)
+    });
+    crate::hlt_loop();
+}
+
+extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    timer::tick();
+    writer::on_timer_tick(timer::ticks());
+
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
+    }
+}
+
+extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    let mut port: Port<u8> = Port::new(0x60);
+    let scancode = unsafe { port.read() };
+    keyboard::add_scancode(scancode);
+
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8());
+    }
+}
@@ -0,0 +1,408 @@
+use crate::writer::constants::font_constants::BACKSPACE;
+use crate::writer::{self, CONSOLE_COUNT};
+use x86_64::instructions::interrupts;
+
+/// Capacity of the scancode queue filled by the keyboard interrupt handler.
+const QUEUE_SIZE: usize = 128;
+
+/// Prefix byte of extended scancodes.
+const EXTENDED_PREFIX: u8 = 0xE0;
+
+/// Prefix byte of the Pause key sequence, which has no break code.
+const PAUSE_PREFIX: u8 = 0xE1;
+
+/// Bit set in a scancode when the key is released.
+const BREAK_BIT: u8 = 0x80;
+
+/// A fixed-size ring buffer of raw scancodes.
+struct ScancodeQueue {
+    buffer: [u8; QUEUE_SIZE],
+    head: usize,
+    len: usize,
+}
+
+impl ScancodeQueue {
+    const fn new() -> Self {
+        ScancodeQueue {
+            buffer: [0; QUEUE_SIZE],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Appends a scancode, dropping it if the queue is full.
+    fn push(&mut self, scancode: u8) {
+        if self.len == QUEUE_SIZE {
+            return;
+        }
+        self.buffer[(self.head + self.len) % QUEUE_SIZE] = scancode;
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let scancode = self.buffer[self.head];
+        self.head = (self.head + 1) % QUEUE_SIZE;
+        self.len -= 1;
+        Some(scancode)
+    }
+}
+
+static SCANCODES: spin::Mutex<ScancodeQueue> = spin::Mutex::new(ScancodeQueue::new());
+
+/// Queues a scancode read by the keyboard interrupt handler.
+pub(crate) fn add_scancode(scancode: u8) {
+    SCANCODES.lock().push(scancode);
+}
+
+/// Removes the oldest queued scancode without waiting.
+fn pop_scancode() -> Option<u8> {
+    interrupts::without_interrupts(|| SCANCODES.lock().pop())
+}
+
+/// A physical key, independent of the modifier state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCode {
+    /// A key producing a character, identified by its unshifted character.
+    Char(char),
+    Escape,
+    Backspace,
+    Tab,
+    Enter,
+    LeftCtrl,
+    RightCtrl,
+    LeftShift,
+    RightShift,
+    LeftAlt,
+    RightAlt,
+    CapsLock,
+    NumLock,
+    ScrollLock,
+    /// A function key, numbered from 1 to 12.
+    F(u8),
+    ArrowUp,
+    ArrowDown,
+    ArrowLeft,
+    ArrowRight,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+}
+
+/// State of the modifier keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub caps_lock: bool,
+}
+
+/// A decoded key press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    /// The key that was pressed.
+    pub code: KeyCode,
+    /// The character the key produces under the current modifiers, if any.
+    pub ch: Option<char>,
+    /// The modifiers held while the key was pressed.
+    pub modifiers: Modifiers,
+}
+
+/// A decoder turning scancode set 1 bytes into key presses.
+pub struct Keyboard {
+    modifiers: Modifiers,
+    left_shift: bool,
+    right_shift: bool,
+    left_ctrl: bool,
+    right_ctrl: bool,
+    left_alt: bool,
+    right_alt: bool,
+    extended: bool,
+    skip: u8,
+}
+
+impl Keyboard {
+    /// Creates a decoder with no modifiers held.
+    pub const fn new() -> Self {
+        Keyboard {
+            modifiers: Modifiers {
+                shift: false,
+                ctrl: false,
+                alt: false,
+                caps_lock: false,
+            },
+            left_shift: false,
+            right_shift: false,
+            left_ctrl: false,
+            right_ctrl: false,
+            left_alt: false,
+            right_alt: false,
+            extended: false,
+            skip: 0,
+        }
+    }
+
+    /// Waits for the next key press, halting the CPU while the queue is empty.
+    pub fn read_key(&mut self) -> Key {
+        loop {
+            if let Some(key) = self.poll_key() {
+                return key;
+            }
+            // Check and halt with interrupts disabled so a key arriving in
+            // between cannot be missed until the next timer tick.
+            interrupts::disable();
+            if SCANCODES.lock().len == 0 {
+                interrupts::enable_and_hlt();
+            } else {
+                interrupts::enable();
+            }
+        }
+    }
+
+    /// Decodes queued scancodes until a key press is found or the queue is empty.
+    ///
+    /// Alt+F1 to Alt+F4 switch virtual consoles and Shift+PageUp and
+    /// Shift+PageDown scroll through the console's scrollback; these are not
+    /// reported.
+    pub fn poll_key(&mut self) -> Option<Key> {
+        while let Some(scancode) = pop_scancode() {
+            if let Some(key) = self.process_scancode(scancode) {
+                match key.code {
+                    KeyCode::F(n)
+                        if key.modifiers.alt && (1..=CONSOLE_COUNT).contains(&(n as usize)) =>
+                    {
+                        writer::switch_console(n as usize - 1);
+                    }
+                    KeyCode::PageUp if key.modifiers.shift => {
+                        writer::with_console(|console| console.active_terminal().page_up());
+                    }
+                    KeyCode::PageDown if key.modifiers.shift => {
+                        writer::with_console(|console| console.active_terminal().page_down());
+                    }
+                    _ => return Some(key),
+                }
+            }
+        }
+        None
+    }
+
+    /// Feeds a single scancode to the decoder, returning a key on a completed press.
+    pub fn process_scancode(&mut self, scancode: u8) -> Option<Key> {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return None;
+        }
+        match scancode {
+            EXTENDED_PREFIX => {
+                self.extended = true;
+                return None;
+            }
+            PAUSE_PREFIX => {
+                // Pause sends `E1 1D 45 E1 9D C5` and is not reported.
+                self.skip = 5;
+                return None;
+            }
+            _ => {}
+        }
+
+        let extended = core::mem::replace(&mut self.extended, false);
+        let pressed = scancode & BREAK_BIT == 0;
+        let code = if extended {
+            extended_key(scancode & !BREAK_BIT)?
+        } else {
+            key(scancode & !BREAK_BIT)?
+        };
+
+        if self.update_modifiers(code, pressed) || !pressed {
+            return None;
+        }
+
+        Some(Key {
+            code,
+            ch: self.character(code),
+            modifiers: self.modifiers,
+        })
+    }
+
+    /// Tracks modifier keys, returning whether the key was a modifier.
+    fn update_modifiers(&mut self, code: KeyCode, pressed: bool) -> bool {
+        match code {
+            KeyCode::LeftShift => self.left_shift = pressed,
+            KeyCode::RightShift => self.right_shift = pressed,
+            KeyCode::LeftCtrl => self.left_ctrl = pressed,
+            KeyCode::RightCtrl => self.right_ctrl = pressed,
+            KeyCode::LeftAlt => self.left_alt = pressed,
+            KeyCode::RightAlt => self.right_alt = pressed,
+            KeyCode::CapsLock => {
+                if pressed {
+                    self.modifiers.caps_lock = !self.modifiers.caps_lock;
+                }
+            }
+            _ => return false,
+        }
+        self.modifiers.shift = self.left_shift || self.right_shift;
+        self.modifiers.ctrl = self.left_ctrl || self.right_ctrl;
+        self.modifiers.alt = self.left_alt || self.right_alt;
+        true
+    }
+
+    /// Translates a key into the character it produces under the current modifiers.
+    fn character(&self, code: KeyCode) -> Option<char> {
+        match code {
+            KeyCode::Char(c) if c.is_ascii_alphabetic() => {
+                if self.modifiers.ctrl {
+                    // Ctrl+A..Ctrl+Z produce the control characters 0x01..0x1A.
+                    return Some((c as u8 - b'a' + 1) as char);
+                }
+                if self.modifiers.shift != self.modifiers.caps_lock {
+                    Some(c.to_ascii_uppercase())
+                } else {
+                    Some(c)
+                }
+            }
+            KeyCode::Char(c) if self.modifiers.shift => Some(shifted(c)),
+            KeyCode::Char(c) => Some(c),
+            KeyCode::Enter => Some('\n'),
+            KeyCode::Tab => Some('\t'),
+            KeyCode::Backspace => Some(BACKSPACE),
+            KeyCode::Escape => Some('\u{1b}'),
+            _ => None,
+        }
+    }
+}
+
+/// Maps a scancode set 1 make code to a key.
+fn key(scancode: u8) -> Option<KeyCode> {
+    use KeyCode::*;
+    let code = match scancode {
+        0x01 => Escape,
+        0x02..=0x0A => Char((b'1' + scancode - 0x02) as char),
+        0x0B => Char('0'),
+        0x0C => Char('-'),
+        0x0D => Char('='),
+        0x0E => Backspace,
+        0x0F => Tab,
+        0x10 => Char('q'),
+        0x11 => Char('w'),
+        0x12 => Char('e'),
+        0x13 => Char('r'),
+        0x14 => Char('t'),
+        0x15 => Char('y'),
+        0x16 => Char('u'),
+        0x17 => Char('i'),
+        0x18 => Char('o'),
+        0x19 => Char('p'),
+        0x1A => Char('['),
+        0x1B => Char(']'),
+        0x1C => Enter,
+        0x1D => LeftCtrl,
+        0x1E => Char('a'),
+        0x1F => Char('s'),
+        0x20 => Char('d'),
+        0x21 => Char('f'),
+        0x22 => Char('g'),
+        0x23 => Char('h'),
+        0x24 => Char('j'),
+        0x25 => Char('k'),
+        0x26 => Char('l'),
+        0x27 => Char(';'),
+        0x28 => Char('\''),
+        0x29 => Char('`'),
+        0x2A => LeftShift,
+        0x2B => Char('\\'),
+        0x2C => Char('z'),
+        0x2D => Char('x'),
+        0x2E => Char('c'),
+        0x2F => Char('v'),
+        0x30 => Char('b'),
+        0x31 => Char('n'),
+        0x32 => Char('m'),
+        0x33 => Char(','),
+        0x34 => Char('.'),
+        0x35 => Char('/'),
+        0x36 => RightShift,
+        0x37 => Char('*'),
+        0x38 => LeftAlt,
+        0x39 => Char(' '),
+        0x3A => CapsLock,
+        0x3B..=0x44 => F(scancode - 0x3B + 1),
+        0x45 => NumLock,
+        0x46 => ScrollLock,
+        // Keypad, always treated as if Num Lock were on.
+        0x47 => Char('7'),
+        0x48 => Char('8'),
+        0x49 => Char('9'),
+        0x4A => Char('-'),
+        0x4B => Char('4'),
+        0x4C => Char('5'),
+        0x4D => Char('6'),
+        0x4E => Char('+'),
+        0x4F => Char('1'),
+        0x50 => Char('2'),
+        0x51 => Char('3'),
+        0x52 => Char('0'),
+        0x53 => Char('.'),
+        0x57 => F(11),
+        0x58 => F(12),
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Maps the make code following an `E0` prefix to a key.
+fn extended_key(scancode: u8) -> Option<KeyCode> {
+    use KeyCode::*;
+    let code = match scancode {
+        0x1C => Enter,
+        0x1D => RightCtrl,
+        0x35 => Char('/'),
+        0x38 => RightAlt,
+        0x47 => Home,
+        0x48 => ArrowUp,
+        0x49 => PageUp,
+        0x4B => ArrowLeft,
+        0x4D => ArrowRight,
+        0x4F => End,
+        0x50 => ArrowDown,
+        0x51 => PageDown,
+        0x52 => Insert,
+        0x53 => Delete,
+        // Includes the fake shifts sent around Print Screen.
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Returns the character a non-letter key produces with Shift held.
+fn shifted(c: char) -> char {
+    match c {
+        '1' => '!',
+        '2' => '@',
+        '3' => '#',
+        '4' => '$',
+        '5' => '%',
+        '6' => '^',
+        '7' => '&',
+        '8' => '*',
+        '9' => '(',
+        '0' => ')',
+        '-' => '_',
+        '=' => '+',
+        '[' => '{',
+        ']' => '}',
+        ';' => ':',
+        '\'' => '"',
+        '`' => '~',
+        '\\' => '|',
+        ',' => '<',
+        '.' => '>',
+        '/' => '?',
+        other => other,
+    }
+}
@@ -0,0 +1,42 @@
+use crate::keyboard::{Key, KeyCode};
+use crate::writer::constants::font_constants::BACKSPACE;
+use crate::writer::FrameBufferWriter;
+use alloc::string::String;
+
+/// Collects key presses into a line, echoing them to the framebuffer.
+pub struct LineEditor {
+    line: String,
+}
+
+impl LineEditor {
+    /// Creates an editor with an empty line.
+    pub fn new() -> Self {
+        LineEditor {
+            line: String::new(),
+        }
+    }
+
+    /// Handles a key press, returning the finished line once Enter is pressed.
+    pub fn handle_key(&mut self, key: Key, writer: &mut FrameBufferWriter) -> Option<String> {
+        match key.code {
+            KeyCode::Enter => {
+                writer.write_char('\n');
+                Some(core::mem::take(&mut self.line))
+            }
+            KeyCode::Backspace => {
+                if self.line.pop().is_some() {
+                    writer.write_char(BACKSPACE);
+                }
+                None
+            }
+            _ => {
+                // Control characters (including Tab) have no single-cell echo.
+                if let Some(c) = key.ch.filter(|c| !c.is_control()) {
+                    self.line.push(c);
+                    writer.write_char(c);
+                }
+                None
+            }
+        }
+    }
+}
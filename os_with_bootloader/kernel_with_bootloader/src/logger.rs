@@ -0,0 +1,125 @@
+use crate::writer::{self, TextColor};
+use crate::{serial, serial_println, timer};
+use core::fmt::Write;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use spin::Once;
+
+/// Routes `log` records to the framebuffer console and COM1.
+struct KernelLogger;
+
+static LOGGER: KernelLogger = KernelLogger;
+
+/// Number of warnings logged so far.
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of errors logged so far.
+static ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+/// The pane of the log console showing the warning and error counts.
+static STATUS_BAR: Once<usize> = Once::new();
+
+/// Installs the kernel logger with the given maximum level.
+pub fn init(max_level: LevelFilter) {
+    log::set_logger(&LOGGER).expect("logger already initialized");
+    set_max_level(max_level);
+}
+
+/// Changes the most verbose level that is still logged.
+pub fn set_max_level(max_level: LevelFilter) {
+    log::set_max_level(max_level);
+}
+
+/// Splits a status bar off the top of the log console, counting the warnings
+/// and errors logged so far. Does nothing without a console.
+pub fn init_status_bar() {
+    let pane = writer::with_console(|console| console.add_status_bar(writer::LOG_CONSOLE));
+    let Some(Some(pane)) = pane else {
+        return;
+    };
+    writer::with_pane(writer::LOG_CONSOLE, pane, |bar| {
+        bar.set_color(TextColor::Black);
+        bar.set_background(TextColor::White);
+        bar.clear();
+    });
+    STATUS_BAR.call_once(|| pane);
+    update_status_bar();
+}
+
+/// Writes the current counts over the status bar, if there is one.
+fn update_status_bar() {
+    let Some(&pane) = STATUS_BAR.get() else {
+        return;
+    };
+    writer::with_pane(writer::LOG_CONSOLE, pane, |bar| {
+        bar.set_cursor_position(0, 0);
+        let _ = write!(
+            bar,
+            " {} warnings, {} errors\x1b[K",
+            WARNINGS.load(Ordering::Relaxed),
+            ERRORS.load(Ordering::Relaxed)
+        );
+    });
+}
+
+/// Picks the console color of a log level.
+fn level_color(level: Level) -> TextColor {
+    match level {
+        Level::Error => TextColor::Red,
+        Level::Warn => TextColor::Yellow,
+        Level::Info => TextColor::Green,
+        Level::Debug => TextColor::Blue,
+        Level::Trace => TextColor::White,
+    }
+}
+
+impl Log for KernelLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let uptime = timer::uptime_ms();
+        let (seconds, millis) = (uptime / 1000, uptime % 1000);
+
+        let written_to_console = writer::with_terminal(writer::LOG_CONSOLE, |writer| {
+            let previous_color = writer.color();
+            writer.set_color(level_color(record.level()));
+            let _ = write!(
+                writer,
+                "[{:>5}.{:03}] {:<5} {}",
+                seconds,
+                millis,
+                record.level(),
+                record.args()
+            );
+            writer.newline();
+            writer.set_color(previous_color);
+        });
+
+        // The console already mirrors everything to serial when teeing is enabled.
+        if written_to_console.is_none() || !serial::console_tee_enabled() {
+            serial_println!(
+                "[{:>5}.{:03}] {:<5} {}",
+                seconds,
+                millis,
+                record.level(),
+                record.args()
+            );
+        }
+
+        let count = match record.level() {
+            Level::Warn => &WARNINGS,
+            Level::Error => &ERRORS,
+            _ => return,
+        };
+        count.fetch_add(1, Ordering::Relaxed);
+        update_status_bar();
+    }
+
+    fn flush(&self) {}
+}
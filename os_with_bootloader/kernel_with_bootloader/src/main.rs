@@ -1,14 +1,33 @@
-#![no_std]
-#![no_main]
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
+#![feature(abi_x86_interrupt)]
+// Unit tests run on the host with `cargo test-host`, where nothing calls
+// `kernel_main` and most of the kernel is unreachable.
+#![cfg_attr(test, allow(dead_code))]
 
+extern crate alloc;
+
+mod allocator;
+mod gdt;
+mod graphics;
+mod interrupts;
+mod keyboard;
+mod line_editor;
+mod logger;
+mod memory;
+mod panic_screen;
+mod serial;
+mod timer;
 mod writer;
 
 use bootloader_api::{config::Mapping, BootloaderConfig, BootInfo};
-use writer::{FrameBufferWriter, TextColor};
-use x86_64::instructions::hlt;
-use core::fmt::Write;
+use keyboard::Keyboard;
+use line_editor::LineEditor;
+use log::LevelFilter;
+use x86_64::{instructions::hlt, VirtAddr};
 
-bootloader_api::entry_point!(kernel_main);
+#[cfg(not(test))]
+bootloader_api::entry_point!(kernel_main, config = &BOOTLOADER_CONFIG);
 
 pub static BOOTLOADER_CONFIG: BootloaderConfig = {
     let mut config = BootloaderConfig::new_default();
@@ -18,63 +37,75 @@ pub static BOOTLOADER_CONFIG: BootloaderConfig = {
 };
 
 fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
-    let framebuffer = boot_info
-        .framebuffer
-        .as_mut()
-        .unwrap_or_else(|| loop { hlt(); });
-    let fb_info = framebuffer.info();
-    let buffer = framebuffer.buffer_mut();
-    let mut frame_buffer_writer = FrameBufferWriter::new(buffer, fb_info);
+    serial::init();
+    logger::init(LevelFilter::Info);
+    log::info!("kernel_with_bootloader: booting");
 
-    // Use a raw string literal so that our custom escape sequences are preserved.
-    print!(
-        &mut frame_buffer_writer,
-        r"watashi no Soul Society!\nTesting Testing Tester Tested.\n\cBlue Blue Text\tTabbed Text"
+    gdt::init();
+    interrupts::init_idt();
+    interrupts::init_pics();
+    timer::init();
+    x86_64::instructions::interrupts::enable();
+
+    let physical_memory_offset = VirtAddr::new(
+        boot_info
+            .physical_memory_offset
+            .into_option()
+            .expect("physical memory mapping not provided by the bootloader"),
     );
+    unsafe {
+        memory::init_frame_allocator(&boot_info.memory_regions, physical_memory_offset);
+        memory::init_memory_manager(physical_memory_offset);
+    }
+    if let Some(memory_manager) = memory::MEMORY_MANAGER.lock().as_mut() {
+        if let Err(err) = allocator::init_heap(memory_manager) {
+            panic!("heap initialization failed: {}", err);
+        }
+        if let Err(err) = gdt::init_double_fault_stack(memory_manager) {
+            log::warn!("Double fault stack has no guard page: {}", err);
+        }
+    }
 
-    loop {
-        hlt();
+    if let Some(framebuffer) = boot_info.framebuffer.as_mut() {
+        let fb_info = framebuffer.info();
+        writer::init(framebuffer.buffer_mut(), fb_info);
+        // The heap is up, so rendering can go through a back buffer in RAM.
+        if let Some(Err(err)) = writer::with_console(|console| console.enable_back_buffer()) {
+            log::warn!("Console back buffer disabled: {}", err);
+        }
+        logger::init_status_bar();
+        if let Err(err) = graphics::splash::show() {
+            log::warn!("Boot splash not shown: {}", err);
+        }
+    } else {
+        log::warn!("No framebuffer available, console output goes to serial only");
     }
-}
 
-macro_rules! print {
-    ($writer:expr, $($arg:tt)*) => {{
-        // Format the input into a single string.
-        let formatted = format!($($arg)*);
-        let mut chars = formatted.chars().peekable();
-        while let Some(c) = chars.next() {
-            if c == '\\' {
-                if let Some(next) = chars.next() {
-                    match next {
-                        'n' => $writer.newline(),
-                        't' => $writer.write_tab(),
-                        'c' => {
-                            // Gather alphabetic characters for the color name.
-                            let mut color_name = String::new();
-                            while let Some(&ch) = chars.peek() {
-                                if ch.is_alphabetic() {
-                                    color_name.push(ch);
-                                    chars.next();
-                                } else {
-                                    break;
-                                }
-                            }
-                            if let Some(color) = TextColor::from_str(&color_name) {
-                                $writer.set_color(color);
-                            }
-                        }
-                        other => $writer.write_char(other),
-                    }
-                }
-            } else {
-                $writer.write_char(c);
-            }
+    // Use a raw string literal so that our custom escape sequences are preserved.
+    print!(r"watashi no Soul Society!\nTesting Testing Tester Tested.\n\cBlue Blue Text\tTabbed Text\n");
+    if let Some(frame_allocator) = memory::FRAME_ALLOCATOR.lock().as_ref() {
+        let stats = frame_allocator.stats();
+        log::info!(
+            "Memory: {} KiB total, {} KiB used, {} KiB free",
+            stats.total / 1024,
+            stats.used / 1024,
+            stats.free / 1024
+        );
+    }
+    print!(r"\cWhite> ");
+
+    let mut keyboard = Keyboard::new();
+    let mut line_editor = LineEditor::new();
+    loop {
+        let key = keyboard.read_key();
+        if let Some(Some(_line)) = writer::with_writer(|w| line_editor.handle_key(key, w)) {
+            print!("> ");
         }
-    }};
+    }
 }
 
-#[panic_handler]
-fn panic(_info: &core::panic::PanicInfo) -> ! {
+/// Halts the CPU until the next interrupt, forever.
+pub fn hlt_loop() -> ! {
     loop {
         hlt();
     }
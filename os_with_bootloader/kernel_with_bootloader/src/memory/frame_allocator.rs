@@ -0,0 +1,156 @@
+use bootloader_api::info::{MemoryRegionKind, MemoryRegions};
+use core::slice;
+use x86_64::{
+    align_up,
+    structures::paging::{FrameAllocator, FrameDeallocator, PhysFrame, Size4KiB},
+    PhysAddr, VirtAddr,
+};
+
+/// Size of a physical frame.
+pub const FRAME_SIZE: u64 = 4096;
+
+/// Number of frames tracked by one word of the bitmap.
+const BITS_PER_WORD: usize = u64::BITS as usize;
+
+/// Physical memory usage reported by the frame allocator.
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryStats {
+    /// Bytes of usable physical memory.
+    pub total: u64,
+    /// Bytes handed out, including the bitmap itself.
+    pub used: u64,
+    /// Bytes still available for allocation.
+    pub free: u64,
+}
+
+/// A frame allocator keeping one bit per physical frame, set when the frame is in use.
+pub struct BitmapFrameAllocator {
+    bitmap: &'static mut [u64],
+    usable_frames: usize,
+    used_frames: usize,
+    next_word: usize,
+}
+
+impl BitmapFrameAllocator {
+    /// Creates an allocator for the usable regions of the memory map.
+    ///
+    /// The bitmap is stored in the first usable region large enough to hold it,
+    /// accessed through the physical memory mapping.
+    ///
+    /// # Safety
+    /// The caller must guarantee that all frames marked as `Usable` in the memory
+    /// map are really unused and that the complete physical memory is mapped at
+    /// `physical_memory_offset`.
+    pub unsafe fn init(memory_regions: &MemoryRegions, physical_memory_offset: VirtAddr) -> Self {
+        let usable = || {
+            memory_regions
+                .iter()
+                .filter(|region| region.kind == MemoryRegionKind::Usable)
+        };
+
+        let frame_count = usable().map(|region| region.end).max().unwrap_or(0) / FRAME_SIZE;
+        let words = (frame_count as usize).div_ceil(BITS_PER_WORD);
+        let bitmap_bytes = (words * core::mem::size_of::<u64>()) as u64;
+
+        let bitmap_start = usable()
+            .map(|region| (align_up(region.start, FRAME_SIZE), region.end))
+            .find(|&(start, end)| start + bitmap_bytes <= end)
+            .map(|(start, _)| start)
+            .expect("no usable region can hold the frame bitmap");
+
+        let bitmap_ptr = (physical_memory_offset + bitmap_start).as_mut_ptr::<u64>();
+        let bitmap = unsafe { slice::from_raw_parts_mut(bitmap_ptr, words) };
+        // Everything is in use until the memory map says otherwise.
+        bitmap.fill(u64::MAX);
+
+        let mut allocator = BitmapFrameAllocator {
+            bitmap,
+            usable_frames: 0,
+            used_frames: 0,
+            next_word: 0,
+        };
+
+        for region in usable() {
+            let first = align_up(region.start, FRAME_SIZE) / FRAME_SIZE;
+            let last = region.end / FRAME_SIZE;
+            for frame in first..last {
+                allocator.set_used(frame as usize, false);
+                allocator.usable_frames += 1;
+            }
+        }
+
+        // Never hand out the frame at physical address zero.
+        if !allocator.is_used(0) {
+            allocator.set_used(0, true);
+            allocator.used_frames += 1;
+        }
+
+        // The bitmap may start at frame zero, which is already counted above.
+        let bitmap_frames = bitmap_bytes.div_ceil(FRAME_SIZE);
+        for frame in 0..bitmap_frames {
+            let index = (bitmap_start / FRAME_SIZE + frame) as usize;
+            if !allocator.is_used(index) {
+                allocator.set_used(index, true);
+                allocator.used_frames += 1;
+            }
+        }
+
+        allocator
+    }
+
+    /// Returns whether the given frame index is marked as in use.
+    fn is_used(&self, index: usize) -> bool {
+        self.bitmap[index / BITS_PER_WORD] & (1 << (index % BITS_PER_WORD)) != 0
+    }
+
+    fn set_used(&mut self, index: usize, used: bool) {
+        let word = &mut self.bitmap[index / BITS_PER_WORD];
+        let bit = 1 << (index % BITS_PER_WORD);
+        if used {
+            *word |= bit;
+        } else {
+            *word &= !bit;
+        }
+    }
+
+    /// Reports the total, used and free usable memory.
+    pub fn stats(&self) -> MemoryStats {
+        let total = self.usable_frames as u64 * FRAME_SIZE;
+        let used = self.used_frames as u64 * FRAME_SIZE;
+        MemoryStats {
+            total,
+            used,
+            free: total - used,
+        }
+    }
+}
+
+unsafe impl FrameAllocator<Size4KiB> for BitmapFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame> {
+        let words = self.bitmap.len();
+        // Start at the last word that had room, wrapping around once.
+        let word_index = (0..words)
+            .map(|offset| (self.next_word + offset) % words)
+            .find(|&index| self.bitmap[index] != u64::MAX)?;
+
+        let bit = self.bitmap[word_index].trailing_ones() as usize;
+        let index = word_index * BITS_PER_WORD + bit;
+        self.set_used(index, true);
+        self.used_frames += 1;
+        self.next_word = word_index;
+
+        Some(PhysFrame::containing_address(PhysAddr::new(
+            index as u64 * FRAME_SIZE,
+        )))
+    }
+}
+
+impl FrameDeallocator<Size4KiB> for BitmapFrameAllocator {
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame) {
+        let index = (frame.start_address().as_u64() / FRAME_SIZE) as usize;
+        assert!(self.is_used(index), "double free of frame {:?}", frame);
+        self.set_used(index, false);
+        self.used_frames -= 1;
+        self.next_word = self.next_word.min(index / BITS_PER_WORD);
+    }
+}
@@ -0,0 +1,61 @@
+pub mod frame_allocator;
+pub mod paging;
+
+use bootloader_api::info::MemoryRegions;
+use frame_allocator::BitmapFrameAllocator;
+use paging::MemoryManager;
+use x86_64::{
+    registers::control::Cr3,
+    structures::paging::{OffsetPageTable, PageTable},
+    VirtAddr,
+};
+
+/// The global physical frame allocator, set up by `init_frame_allocator`.
+pub static FRAME_ALLOCATOR: spin::Mutex<Option<BitmapFrameAllocator>> = spin::Mutex::new(None);
+
+/// The global memory manager for the kernel address space, set up by `init_memory_manager`.
+pub static MEMORY_MANAGER: spin::Mutex<Option<MemoryManager>> = spin::Mutex::new(None);
+
+/// Builds the global frame allocator from the bootloader's memory map.
+///
+/// # Safety
+/// Same requirements as `BitmapFrameAllocator::init`; must only be called once.
+pub unsafe fn init_frame_allocator(
+    memory_regions: &MemoryRegions,
+    physical_memory_offset: VirtAddr,
+) {
+    let allocator = unsafe { BitmapFrameAllocator::init(memory_regions, physical_memory_offset) };
+    *FRAME_ALLOCATOR.lock() = Some(allocator);
+}
+
+/// Initializes an `OffsetPageTable` over the active level 4 table.
+///
+/// # Safety
+/// The caller must guarantee that the complete physical memory is mapped at
+/// `physical_memory_offset`, and this must only be called once.
+pub unsafe fn init(physical_memory_offset: VirtAddr) -> OffsetPageTable<'static> {
+    unsafe {
+        let level_4_table = active_level_4_table(physical_memory_offset);
+        OffsetPageTable::new(level_4_table, physical_memory_offset)
+    }
+}
+
+/// Returns a mutable reference to the active level 4 table.
+unsafe fn active_level_4_table(physical_memory_offset: VirtAddr) -> &'static mut PageTable {
+    let (level_4_table_frame, _) = Cr3::read();
+
+    let phys = level_4_table_frame.start_address();
+    let virt = physical_memory_offset + phys.as_u64();
+    let page_table_ptr: *mut PageTable = virt.as_mut_ptr();
+
+    unsafe { &mut *page_table_ptr }
+}
+
+/// Sets up the global memory manager over the active page table.
+///
+/// # Safety
+/// Same requirements as `init`; must only be called once.
+pub unsafe fn init_memory_manager(physical_memory_offset: VirtAddr) {
+    let mapper = unsafe { init(physical_memory_offset) };
+    *MEMORY_MANAGER.lock() = Some(MemoryManager::new(mapper));
+}
@@ -0,0 +1,296 @@
+use super::{frame_allocator::BitmapFrameAllocator, FRAME_ALLOCATOR};
+use core::fmt;
+use x86_64::{
+    align_up,
+    structures::paging::{
+        mapper::{FlagUpdateError, MapToError, UnmapError},
+        FrameAllocator, FrameDeallocator, Mapper, OffsetPageTable, Page, PageTableFlags, Size4KiB,
+        Translate,
+    },
+    PhysAddr, VirtAddr,
+};
+
+/// Size of a virtual page.
+pub const PAGE_SIZE: u64 = 4096;
+
+/// Access permissions of a mapped range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permissions {
+    pub writable: bool,
+    pub executable: bool,
+    pub user: bool,
+}
+
+// The read-only and code permissions are for `protect`, once sections are
+// mapped by the kernel.
+#[allow(dead_code)]
+impl Permissions {
+    /// Kernel read-only data.
+    pub const READ_ONLY: Permissions = Permissions {
+        writable: false,
+        executable: false,
+        user: false,
+    };
+
+    /// Kernel code.
+    pub const KERNEL_CODE: Permissions = Permissions {
+        writable: false,
+        executable: true,
+        user: false,
+    };
+}
+
+impl Permissions {
+    /// Kernel read-write data, such as heaps and stacks.
+    pub const KERNEL_DATA: Permissions = Permissions {
+        writable: true,
+        executable: false,
+        user: false,
+    };
+
+    /// Converts the permissions into leaf page table flags.
+    fn page_flags(self) -> PageTableFlags {
+        let mut flags = PageTableFlags::PRESENT;
+        if self.writable {
+            flags |= PageTableFlags::WRITABLE;
+        }
+        if !self.executable {
+            flags |= PageTableFlags::NO_EXECUTE;
+        }
+        if self.user {
+            flags |= PageTableFlags::USER_ACCESSIBLE;
+        }
+        flags
+    }
+
+    /// Flags for page tables created on the way to a leaf entry; these must be
+    /// at least as permissive as any leaf below them.
+    fn parent_flags(self) -> PageTableFlags {
+        let mut flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+        if self.user {
+            flags |= PageTableFlags::USER_ACCESSIBLE;
+        }
+        flags
+    }
+}
+
+/// Errors returned by the memory manager.
+#[derive(Debug)]
+pub enum MemoryError {
+    /// The frame allocator is not initialized or ran out of frames.
+    FrameAllocationFailed,
+    /// The page is already mapped.
+    AlreadyMapped(Page),
+    /// The page is not mapped.
+    NotMapped(Page),
+    /// The page is part of a huge page mapping that cannot be changed per 4 KiB page.
+    HugePage(Page),
+}
+
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MemoryError::FrameAllocationFailed => write!(f, "no physical frame available"),
+            MemoryError::AlreadyMapped(page) => {
+                write!(f, "page {:#x} is already mapped", page.start_address())
+            }
+            MemoryError::NotMapped(page) => {
+                write!(f, "page {:#x} is not mapped", page.start_address())
+            }
+            MemoryError::HugePage(page) => {
+                write!(f, "page {:#x} is part of a huge page", page.start_address())
+            }
+        }
+    }
+}
+
+/// A bottom-up range of mapped stack memory.
+#[derive(Debug, Clone, Copy)]
+pub struct StackBounds {
+    /// Lowest mapped address, directly above the guard page.
+    pub bottom: VirtAddr,
+    /// Address past the highest mapped byte, where the stack pointer starts.
+    pub top: VirtAddr,
+}
+
+/// Maps, unmaps and protects virtual memory in the active address space.
+pub struct MemoryManager {
+    mapper: OffsetPageTable<'static>,
+}
+
+impl MemoryManager {
+    /// Creates a memory manager for the given page table mapper.
+    pub fn new(mapper: OffsetPageTable<'static>) -> Self {
+        MemoryManager { mapper }
+    }
+
+    /// Maps `size` bytes starting at `start` to freshly allocated frames.
+    ///
+    /// On failure, the pages mapped so far are unmapped again.
+    pub fn map_range(
+        &mut self,
+        start: VirtAddr,
+        size: u64,
+        permissions: Permissions,
+    ) -> Result<(), MemoryError> {
+        let mut frame_allocator = FRAME_ALLOCATOR.lock();
+        let frame_allocator = frame_allocator
+            .as_mut()
+            .ok_or(MemoryError::FrameAllocationFailed)?;
+
+        for (mapped, page) in pages(start, size).enumerate() {
+            if let Err(err) = self.map_page(page, permissions, frame_allocator) {
+                for page in pages(start, size).take(mapped) {
+                    if let Ok((frame, flush)) = self.mapper.unmap(page) {
+                        flush.flush();
+                        unsafe { frame_allocator.deallocate_frame(frame) };
+                    }
+                }
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Maps a single page to a freshly allocated frame.
+    fn map_page(
+        &mut self,
+        page: Page,
+        permissions: Permissions,
+        frame_allocator: &mut BitmapFrameAllocator,
+    ) -> Result<(), MemoryError> {
+        let frame = frame_allocator
+            .allocate_frame()
+            .ok_or(MemoryError::FrameAllocationFailed)?;
+        let result = unsafe {
+            self.mapper.map_to_with_table_flags(
+                page,
+                frame,
+                permissions.page_flags(),
+                permissions.parent_flags(),
+                frame_allocator,
+            )
+        };
+        match result {
+            Ok(flush) => {
+                flush.flush();
+                Ok(())
+            }
+            Err(err) => {
+                unsafe { frame_allocator.deallocate_frame(frame) };
+                Err(map_error(page, err))
+            }
+        }
+    }
+
+    /// Unmaps `size` bytes starting at `start` and frees the backing frames.
+    ///
+    /// # Safety
+    /// The range must have been mapped with `map_range` and must no longer be
+    /// referenced by anything.
+    #[allow(dead_code)] // Nothing is unmapped yet; the heap and stacks live forever.
+    pub unsafe fn unmap_range(&mut self, start: VirtAddr, size: u64) -> Result<(), MemoryError> {
+        let mut frame_allocator = FRAME_ALLOCATOR.lock();
+        let frame_allocator = frame_allocator
+            .as_mut()
+            .ok_or(MemoryError::FrameAllocationFailed)?;
+
+        for page in pages(start, size) {
+            let (frame, flush) = self
+                .mapper
+                .unmap(page)
+                .map_err(|err| unmap_error(page, err))?;
+            flush.flush();
+            unsafe { frame_allocator.deallocate_frame(frame) };
+        }
+        Ok(())
+    }
+
+    /// Changes the permissions of an already mapped range.
+    ///
+    /// Only the leaf entries are updated, so making kernel pages user accessible
+    /// requires them to have been mapped with user permissions in the first place.
+    #[allow(dead_code)] // Nothing changes permissions yet; kept with `unmap_range`.
+    pub fn protect(
+        &mut self,
+        start: VirtAddr,
+        size: u64,
+        permissions: Permissions,
+    ) -> Result<(), MemoryError> {
+        for page in pages(start, size) {
+            let flush = unsafe { self.mapper.update_flags(page, permissions.page_flags()) }
+                .map_err(|err| flag_update_error(page, err))?;
+            flush.flush();
+        }
+        Ok(())
+    }
+
+    /// Translates a virtual address to the physical address it is mapped to.
+    pub fn translate(&self, addr: VirtAddr) -> Option<PhysAddr> {
+        self.mapper.translate_addr(addr)
+    }
+
+    /// Returns whether the page containing `addr` is mapped.
+    pub fn is_mapped(&self, addr: VirtAddr) -> bool {
+        self.translate(addr).is_some()
+    }
+
+    /// Maps a kernel stack of `size` bytes above an unmapped guard page at `guard_page`.
+    ///
+    /// Overflowing the stack touches the guard page and raises a page fault
+    /// instead of silently corrupting the memory below.
+    pub fn allocate_stack(
+        &mut self,
+        guard_page: VirtAddr,
+        size: u64,
+    ) -> Result<StackBounds, MemoryError> {
+        let guard = Page::<Size4KiB>::containing_address(guard_page);
+        if self.is_mapped(guard.start_address()) {
+            return Err(MemoryError::AlreadyMapped(guard));
+        }
+
+        let bottom = guard.start_address() + PAGE_SIZE;
+        let size = align_up(size, PAGE_SIZE);
+        self.map_range(bottom, size, Permissions::KERNEL_DATA)?;
+        Ok(StackBounds {
+            bottom,
+            top: bottom + size,
+        })
+    }
+}
+
+/// Returns the pages covering `size` bytes starting at `start`.
+fn pages(start: VirtAddr, size: u64) -> impl Iterator<Item = Page> {
+    let first = Page::containing_address(start);
+    let count = if size == 0 {
+        0
+    } else {
+        let last = Page::<Size4KiB>::containing_address(start + (size - 1));
+        (last.start_address() - first.start_address()) / PAGE_SIZE + 1
+    };
+    (0..count).map(move |index| first + index)
+}
+
+fn map_error(page: Page, err: MapToError<Size4KiB>) -> MemoryError {
+    match err {
+        MapToError::FrameAllocationFailed => MemoryError::FrameAllocationFailed,
+        MapToError::PageAlreadyMapped(_) => MemoryError::AlreadyMapped(page),
+        MapToError::ParentEntryHugePage => MemoryError::HugePage(page),
+    }
+}
+
+fn unmap_error(page: Page, err: UnmapError) -> MemoryError {
+    match err {
+        UnmapError::PageNotMapped | UnmapError::InvalidFrameAddress(_) => {
+            MemoryError::NotMapped(page)
+        }
+        UnmapError::ParentEntryHugePage => MemoryError::HugePage(page),
+    }
+}
+
+fn flag_update_error(page: Page, err: FlagUpdateError) -> MemoryError {
+    match err {
+        FlagUpdateError::PageNotMapped => MemoryError::NotMapped(page),
+        FlagUpdateError::ParentEntryHugePage => MemoryError::HugePage(page),
+    }
+}
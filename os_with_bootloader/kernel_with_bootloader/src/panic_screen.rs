@@ -0,0 +1,105 @@
+use crate::serial::{self, SERIAL1};
+use crate::writer::{TextColor, CONSOLE};
+use core::arch::asm;
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::instructions::{hlt, interrupts};
+use x86_64::registers::control::{Cr0, Cr2, Cr3, Cr4};
+use x86_64::registers::rflags;
+
+/// Set once a panic is being reported, so a panic inside the report only halts.
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// Register state captured on entry to the panic handler.
+struct RegisterDump {
+    rsp: u64,
+    rbp: u64,
+    rflags: u64,
+    cr0: u64,
+    cr2: u64,
+    cr3: u64,
+    cr4: u64,
+}
+
+impl RegisterDump {
+    fn capture() -> Self {
+        let (rsp, rbp): (u64, u64);
+        unsafe {
+            asm!("mov {}, rsp", out(reg) rsp, options(nomem, nostack, preserves_flags));
+            asm!("mov {}, rbp", out(reg) rbp, options(nomem, nostack, preserves_flags));
+        }
+        RegisterDump {
+            rsp,
+            rbp,
+            rflags: rflags::read_raw(),
+            cr0: Cr0::read_raw(),
+            cr2: Cr2::read().as_u64(),
+            cr3: Cr3::read().0.start_address().as_u64(),
+            cr4: Cr4::read_raw(),
+        }
+    }
+}
+
+impl fmt::Display for RegisterDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "RSP={:#018x} RBP={:#018x} RFLAGS={:#018x}",
+            self.rsp, self.rbp, self.rflags
+        )?;
+        writeln!(f, "CR0={:#018x} CR2={:#018x}", self.cr0, self.cr2)?;
+        write!(f, "CR3={:#018x} CR4={:#018x}", self.cr3, self.cr4)
+    }
+}
+
+/// Writes the panic message, its location and the captured registers.
+fn write_report(out: &mut impl Write, info: &PanicInfo, registers: &RegisterDump) -> fmt::Result {
+    writeln!(out, "KERNEL PANIC: {}", info.message())?;
+    match info.location() {
+        Some(location) => writeln!(
+            out,
+            "at {}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        )?,
+        None => writeln!(out, "at an unknown location")?,
+    }
+    writeln!(out, "{}", registers)
+}
+
+#[cfg_attr(not(test), panic_handler)]
+fn panic(info: &PanicInfo) -> ! {
+    interrupts::disable();
+    let registers = RegisterDump::capture();
+
+    if !PANICKING.swap(true, Ordering::SeqCst) {
+        // Nothing interrupted by the panic will ever run again, so any lock it
+        // held is taken over rather than waited for.
+        if SERIAL1.is_locked() {
+            unsafe { SERIAL1.force_unlock() };
+        }
+        let _ = write_report(&mut *SERIAL1.lock(), info, &registers);
+
+        // The report has already been sent to serial in one piece.
+        serial::set_console_tee(false);
+        if CONSOLE.is_locked() {
+            unsafe { CONSOLE.force_unlock() };
+        }
+        if let Some(console) = CONSOLE.lock().as_mut() {
+            let writer = console.active_terminal();
+            writer.reset();
+            writer.set_cursor_visible(false);
+            writer.set_color(TextColor::Red);
+            writer.write_rule();
+            let _ = write_report(writer, info, &registers);
+            writer.write_rule();
+            console.flush();
+        }
+    }
+
+    loop {
+        hlt();
+    }
+}
@@ -0,0 +1,133 @@
+use crate::writer::constants::font_constants::BACKSPACE;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::instructions::port::Port;
+
+/// I/O port base of the first serial port.
+const COM1: u16 = 0x3F8;
+
+/// Line status register bit set when the transmit holding register is empty.
+const LSR_TRANSMIT_EMPTY: u8 = 0x20;
+
+/// A 16550 UART accessed through port I/O.
+pub struct SerialPort {
+    base: u16,
+}
+
+impl SerialPort {
+    /// Creates a handle for the UART at the given I/O port base.
+    ///
+    /// # Safety
+    /// The base must belong to a 16550-compatible UART.
+    pub const unsafe fn new(base: u16) -> Self {
+        SerialPort { base }
+    }
+
+    /// Configures the UART for 38400 baud, 8 data bits, no parity and one stop bit.
+    pub fn init(&mut self) {
+        unsafe {
+            // Disable UART interrupts; output is polled.
+            self.port(1).write(0x00);
+            // Set the baud rate divisor (115200 / 3) with DLAB enabled.
+            self.port(3).write(0x80);
+            self.port(0).write(0x03);
+            self.port(1).write(0x00);
+            // 8 bits, no parity, one stop bit, DLAB disabled.
+            self.port(3).write(0x03);
+            // Enable and clear the FIFOs with a 14 byte threshold.
+            self.port(2).write(0xC7);
+            // Assert DTR and RTS and enable the auxiliary output 2.
+            self.port(4).write(0x0B);
+        }
+    }
+
+    fn port(&self, offset: u16) -> Port<u8> {
+        Port::new(self.base + offset)
+    }
+
+    /// Sends a single byte, waiting until the UART can accept it.
+    pub fn send(&mut self, byte: u8) {
+        unsafe {
+            while self.port(5).read() & LSR_TRANSMIT_EMPTY == 0 {
+                core::hint::spin_loop();
+            }
+            self.port(0).write(byte);
+        }
+    }
+}
+
+impl fmt::Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.send(byte);
+        }
+        Ok(())
+    }
+}
+
+/// The COM1 serial port.
+pub static SERIAL1: spin::Mutex<SerialPort> = spin::Mutex::new(unsafe { SerialPort::new(COM1) });
+
+/// Whether framebuffer console output is mirrored to COM1.
+static CONSOLE_TEE: AtomicBool = AtomicBool::new(true);
+
+/// Initializes COM1.
+pub fn init() {
+    SERIAL1.lock().init();
+}
+
+/// Enables or disables mirroring framebuffer console output to COM1.
+pub fn set_console_tee(enabled: bool) {
+    CONSOLE_TEE.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns whether framebuffer console output is mirrored to COM1.
+pub fn console_tee_enabled() -> bool {
+    CONSOLE_TEE.load(Ordering::Relaxed)
+}
+
+/// Mirrors a character written to the framebuffer console, if enabled.
+pub(crate) fn tee_char(c: char) {
+    if !console_tee_enabled() {
+        return;
+    }
+    let mut buffer = [0; 4];
+    let encoded = match c {
+        // Terminals only move the cursor on a backspace, so also blank the cell.
+        BACKSPACE => "\u{8} \u{8}",
+        c => c.encode_utf8(&mut buffer),
+    };
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut serial = SERIAL1.lock();
+        for byte in encoded.bytes() {
+            serial.send(byte);
+        }
+    });
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    use core::fmt::Write;
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        SERIAL1
+            .lock()
+            .write_fmt(args)
+            .expect("printing to serial failed");
+    });
+}
+
+/// Prints to COM1.
+#[macro_export]
+macro_rules! serial_print {
+    ($($arg:tt)*) => {
+        $crate::serial::_print(format_args!($($arg)*))
+    };
+}
+
+/// Prints to COM1, appending a newline.
+#[macro_export]
+macro_rules! serial_println {
+    () => ($crate::serial_print!("\n"));
+    ($fmt:expr) => ($crate::serial_print!(concat!($fmt, "\n")));
+    ($fmt:expr, $($arg:tt)*) => ($crate::serial_print!(concat!($fmt, "\n"), $($arg)*));
+}
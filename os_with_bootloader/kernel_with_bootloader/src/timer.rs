@@ -0,0 +1,39 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::instructions::port::Port;
+
+/// Frequency of the timer interrupt.
+pub const TIMER_HZ: u32 = 100;
+
+/// Base frequency of the programmable interval timer.
+const PIT_BASE_FREQUENCY: u32 = 1_193_182;
+
+/// Number of timer interrupts since boot.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Programs channel 0 of the PIT to fire at `TIMER_HZ`.
+pub fn init() {
+    let divisor = (PIT_BASE_FREQUENCY / TIMER_HZ) as u16;
+    let mut command: Port<u8> = Port::new(0x43);
+    let mut channel_0: Port<u8> = Port::new(0x40);
+    unsafe {
+        // Channel 0, low byte then high byte, mode 3 (square wave generator).
+        command.write(0x36);
+        channel_0.write(divisor as u8);
+        channel_0.write((divisor >> 8) as u8);
+    }
+}
+
+/// Advances the tick counter, called from the timer interrupt handler.
+pub(crate) fn tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns the number of timer ticks since boot.
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Returns the time since boot in milliseconds.
+pub fn uptime_ms() -> u64 {
+    ticks() * 1000 / TIMER_HZ as u64
+}
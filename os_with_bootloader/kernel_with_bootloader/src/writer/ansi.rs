@@ -0,0 +1,146 @@
+//! State machine recognizing ANSI/VT100 escape sequences.
+//!
+//! Only the subset understood by `FrameBufferWriter` is interpreted: control
+//! sequences (`ESC [ ... <final>`) and the single character escapes `ESC 7`,
+//! `ESC 8` and `ESC c`. Anything else is swallowed so it never shows up as glyphs.
+
+/// The escape character that starts every sequence.
+pub const ESC: char = '\u{1b}';
+
+/// Maximum number of numeric parameters kept for a control sequence.
+const MAX_PARAMS: usize = 16;
+
+/// A complete control sequence (`ESC [ params final`).
+#[derive(Debug, Clone, Copy)]
+pub struct ControlSequence {
+    params: [u16; MAX_PARAMS],
+    len: usize,
+    /// Set for private sequences such as `ESC [ ? 25 h`.
+    pub private: bool,
+    /// The intermediate character, such as the space in `ESC [ 2 SP q`.
+    pub intermediate: Option<char>,
+    /// The final character selecting the function.
+    pub action: char,
+}
+
+impl ControlSequence {
+    /// The numeric parameters, where omitted ones are 0.
+    pub fn params(&self) -> &[u16] {
+        &self.params[..self.len]
+    }
+
+    /// The parameter at `index`, or `default` if it is omitted or 0.
+    pub fn param(&self, index: usize, default: u16) -> u16 {
+        match self.params().get(index) {
+            Some(&value) if value != 0 => value,
+            _ => default,
+        }
+    }
+}
+
+/// What the writer should do after feeding a character to the parser.
+#[derive(Debug, Clone, Copy)]
+pub enum Action {
+    /// The character is ordinary output.
+    Print(char),
+    /// The character was consumed by an unfinished or ignored sequence.
+    None,
+    /// A control sequence was completed.
+    Control(ControlSequence),
+    /// A single character escape (`ESC <char>`) was completed.
+    Escape(char),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Ground,
+    Escape,
+    Control,
+}
+
+/// Incremental parser fed one character at a time.
+pub struct AnsiParser {
+    state: State,
+    sequence: ControlSequence,
+}
+
+impl AnsiParser {
+    /// Creates a parser in the ground state.
+    pub const fn new() -> Self {
+        AnsiParser {
+            state: State::Ground,
+            sequence: ControlSequence {
+                params: [0; MAX_PARAMS],
+                len: 0,
+                private: false,
+                intermediate: None,
+                action: '\0',
+            },
+        }
+    }
+
+    /// Feeds a character, returning what it amounts to.
+    pub fn advance(&mut self, c: char) -> Action {
+        // An escape always starts over, even in the middle of another sequence.
+        if c == ESC {
+            self.state = State::Escape;
+            return Action::None;
+        }
+
+        match self.state {
+            State::Ground => Action::Print(c),
+            State::Escape => {
+                self.state = State::Ground;
+                match c {
+                    '[' => {
+                        self.state = State::Control;
+                        self.sequence = AnsiParser::new().sequence;
+                        Action::None
+                    }
+                    '7' | '8' | 'c' => Action::Escape(c),
+                    _ => Action::None,
+                }
+            }
+            State::Control => self.advance_control(c),
+        }
+    }
+
+    fn advance_control(&mut self, c: char) -> Action {
+        let sequence = &mut self.sequence;
+        match c {
+            '0'..='9' => {
+                if sequence.len == 0 {
+                    sequence.len = 1;
+                }
+                if let Some(param) = sequence.params.get_mut(sequence.len - 1) {
+                    let digit = c as u16 - '0' as u16;
+                    *param = param.saturating_mul(10).saturating_add(digit);
+                }
+                Action::None
+            }
+            ';' => {
+                // An omitted leading parameter still counts as one.
+                sequence.len = (sequence.len.max(1) + 1).min(MAX_PARAMS);
+                Action::None
+            }
+            '?' if sequence.len == 0 => {
+                sequence.private = true;
+                Action::None
+            }
+            '\u{20}'..='\u{2f}' => {
+                sequence.intermediate = Some(c);
+                Action::None
+            }
+            '\u{40}'..='\u{7e}' => {
+                sequence.action = c;
+                self.state = State::Ground;
+                Action::Control(*sequence)
+            }
+            // Anything else makes the sequence malformed; drop it.
+            _ => {
+                self.state = State::Ground;
+                Action::None
+            }
+        }
+    }
+}
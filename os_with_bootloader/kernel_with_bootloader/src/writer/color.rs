@@ -0,0 +1,145 @@
+/// Supported text colors.
+///
+/// The named colors follow the 16 color ANSI palette; `Rgb` holds any other color.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum TextColor {
+    Black,
+    DarkRed,
+    DarkGreen,
+    DarkYellow,
+    DarkBlue,
+    DarkMagenta,
+    DarkCyan,
+    Gray,
+    DarkGray,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    #[default]
+    White,
+    Rgb(u8, u8, u8),
+}
+
+/// The named colors in ANSI palette order.
+const ANSI_COLORS: [TextColor; 16] = [
+    TextColor::Black,
+    TextColor::DarkRed,
+    TextColor::DarkGreen,
+    TextColor::DarkYellow,
+    TextColor::DarkBlue,
+    TextColor::DarkMagenta,
+    TextColor::DarkCyan,
+    TextColor::Gray,
+    TextColor::DarkGray,
+    TextColor::Red,
+    TextColor::Green,
+    TextColor::Yellow,
+    TextColor::Blue,
+    TextColor::Magenta,
+    TextColor::Cyan,
+    TextColor::White,
+];
+
+/// Names accepted by `TextColor::from_str`, matching `ANSI_COLORS`.
+const COLOR_NAMES: [&str; 16] = [
+    "black",
+    "darkred",
+    "darkgreen",
+    "darkyellow",
+    "darkblue",
+    "darkmagenta",
+    "darkcyan",
+    "gray",
+    "darkgray",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+];
+
+/// Channel levels of the 6x6x6 color cube in the 256 color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl TextColor {
+    /// Background color of a freshly cleared screen.
+    pub const DEFAULT_BACKGROUND: TextColor = TextColor::Black;
+
+    /// Converts a color name (case-insensitively) or a `#rrggbb` hex code into a TextColor.
+    pub fn from_str(color: &str) -> Option<Self> {
+        if let Some(hex) = color.strip_prefix('#') {
+            return Self::from_hex(hex);
+        }
+        COLOR_NAMES
+            .iter()
+            .position(|name| color.eq_ignore_ascii_case(name))
+            .map(|index| ANSI_COLORS[index])
+    }
+
+    /// Parses the `rrggbb` digits of a hex color code.
+    fn from_hex(hex: &str) -> Option<Self> {
+        if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        Some(TextColor::Rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    /// Looks up a color of the 256 color ANSI palette.
+    pub fn from_ansi(index: u8) -> Self {
+        match index {
+            0..=15 => ANSI_COLORS[index as usize],
+            16..=231 => {
+                let cube = index - 16;
+                TextColor::Rgb(
+                    CUBE_LEVELS[(cube / 36) as usize],
+                    CUBE_LEVELS[(cube / 6 % 6) as usize],
+                    CUBE_LEVELS[(cube % 6) as usize],
+                )
+            }
+            232..=255 => {
+                let level = 8 + (index - 232) * 10;
+                TextColor::Rgb(level, level, level)
+            }
+        }
+    }
+
+    /// The red, green and blue components of the color.
+    pub fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            TextColor::Black => (0, 0, 0),
+            TextColor::DarkRed => (170, 0, 0),
+            TextColor::DarkGreen => (0, 170, 0),
+            TextColor::DarkYellow => (170, 170, 0),
+            TextColor::DarkBlue => (0, 0, 170),
+            TextColor::DarkMagenta => (170, 0, 170),
+            TextColor::DarkCyan => (0, 170, 170),
+            TextColor::Gray => (170, 170, 170),
+            TextColor::DarkGray => (85, 85, 85),
+            TextColor::Red => (255, 0, 0),
+            TextColor::Green => (0, 255, 0),
+            TextColor::Yellow => (255, 255, 0),
+            TextColor::Blue => (0, 0, 255),
+            TextColor::Magenta => (255, 0, 255),
+            TextColor::Cyan => (0, 255, 255),
+            TextColor::White => (255, 255, 255),
+            TextColor::Rgb(r, g, b) => (r, g, b),
+        }
+    }
+
+    /// Mixes this color over `background`, where an `alpha` of 0xFF is opaque.
+    pub fn blend(self, background: TextColor, alpha: u8) -> (u8, u8, u8) {
+        let (fr, fg, fb) = self.rgb();
+        let (br, bg, bb) = background.rgb();
+        let mix = |front: u8, back: u8| {
+            let (front, back, alpha) = (front as u16, back as u16, alpha as u16);
+            ((front * alpha + back * (0xFF - alpha) + 0x7F) / 0xFF) as u8
+        };
+        (mix(fr, br), mix(fg, bg), mix(fb, bb))
+    }
+}
@@ -0,0 +1,206 @@
+use crate::graphics::Canvas;
+use crate::writer::display::{Display, Region};
+use crate::writer::font::FontConfig;
+use crate::writer::{FrameBufferWriter, TextColor, BORDER_PADDING};
+use alloc::collections::TryReserveError;
+use alloc::vec;
+use alloc::vec::Vec;
+use bootloader_api::info::FrameBufferInfo;
+
+/// Number of virtual consoles, switched with Alt+F1 to Alt+F4.
+pub const CONSOLE_COUNT: usize = 4;
+
+/// Virtual consoles sharing one display, of which only the active one is drawn.
+///
+/// Each console is split into panes: terminals over regions of the display
+/// with their own cursor and scrolling. A console starts out with a single
+/// pane covering the whole display.
+pub struct Console {
+    display: Display,
+    panes: [Vec<FrameBufferWriter>; CONSOLE_COUNT],
+    active: usize,
+    /// Set when the display has to be cleared before the panes are drawn again,
+    /// so no pixels are left where no pane of the active console is.
+    clear_pending: bool,
+}
+
+impl Console {
+    /// Creates the consoles on the given framebuffer, with the first one active.
+    pub fn new(framebuffer: &'static mut [u8], info: FrameBufferInfo) -> Self {
+        let display = Display::new(framebuffer, info);
+        let panes = core::array::from_fn(|_| vec![FrameBufferWriter::new(display.region())]);
+        Console {
+            display,
+            panes,
+            active: 0,
+            clear_pending: false,
+        }
+    }
+
+    /// Draws through a back buffer in RAM; see `Display::enable_back_buffer`.
+    pub fn enable_back_buffer(&mut self) -> Result<(), TryReserveError> {
+        self.display.enable_back_buffer()
+    }
+
+    /// The whole display area, for laying out panes.
+    pub fn region(&self) -> Region {
+        self.display.region()
+    }
+
+    /// The first pane of a console, which keeps its state while not on screen.
+    pub fn terminal(&mut self, index: usize) -> Option<&mut FrameBufferWriter> {
+        self.pane(index, 0)
+    }
+
+    /// The first pane of the console on screen.
+    pub fn active_terminal(&mut self) -> &mut FrameBufferWriter {
+        &mut self.panes[self.active][0]
+    }
+
+    /// A pane of a console.
+    pub fn pane(&mut self, index: usize, pane: usize) -> Option<&mut FrameBufferWriter> {
+        self.panes.get_mut(index)?.get_mut(pane)
+    }
+
+    /// Adds a pane over a region of a console, clipped to the display, and
+    /// returns its index.
+    ///
+    /// Panes should not overlap; where they do, each draws over the others.
+    /// Make room by moving existing panes with `set_pane_region` first.
+    pub fn add_pane(&mut self, index: usize, region: Region) -> Option<usize> {
+        let region = region.intersect(&self.display.region());
+        let panes = self.panes.get_mut(index)?;
+        panes.push(FrameBufferWriter::new(region));
+        Some(panes.len() - 1)
+    }
+
+    /// Moves a pane to another region, clipped to the display, and redraws the
+    /// console so no stale pixels are left behind.
+    pub fn set_pane_region(&mut self, index: usize, pane: usize, region: Region) {
+        let region = region.intersect(&self.display.region());
+        if let Some(writer) = self.pane(index, pane) {
+            writer.set_region(region);
+            self.redraw(index);
+        }
+    }
+
+    /// Splits a one line pane off the top of a console's first pane, e.g. for
+    /// status information, and returns its index.
+    ///
+    /// The bar hides its cursor and does not echo to serial, so rewriting it
+    /// does not clutter the serial log.
+    pub fn add_status_bar(&mut self, index: usize) -> Option<usize> {
+        let region = self.terminal(index)?.region();
+        // A single line of text, with the border around the grid.
+        let height = FontConfig::DEFAULT.char_height() + 2 * BORDER_PADDING;
+        let (bar, rest) = region.split_top(height);
+        self.set_pane_region(index, 0, rest);
+        let pane = self.add_pane(index, bar)?;
+        let writer = self.pane(index, pane)?;
+        writer.set_serial_echo(false);
+        writer.set_cursor_visible(false);
+        Some(pane)
+    }
+
+    /// A canvas for drawing directly onto a region of the display.
+    ///
+    /// Panes of the console on screen draw over it as their text changes and
+    /// switching consoles clears it, so graphics meant to stay belong outside
+    /// of the panes.
+    pub fn canvas(&mut self, region: Region) -> Canvas<'_> {
+        Canvas::new(&mut self.display, region)
+    }
+
+    /// Puts another console on screen; indices without a console are ignored.
+    pub fn switch_to(&mut self, index: usize) {
+        if index < CONSOLE_COUNT && index != self.active {
+            self.active = index;
+            self.redraw(index);
+        }
+    }
+
+    /// Clears the display and draws every pane of a console again on its
+    /// next flush.
+    fn redraw(&mut self, index: usize) {
+        for pane in &mut self.panes[index] {
+            pane.redraw();
+        }
+        if index == self.active {
+            self.clear_pending = true;
+        }
+    }
+
+    /// Switches the blinking cursors of the console on screen to their next phase.
+    pub fn blink(&mut self) {
+        for pane in &mut self.panes[self.active] {
+            pane.blink();
+        }
+    }
+
+    /// Draws the changes of the console on screen and shows them.
+    pub fn flush(&mut self) {
+        if self.clear_pending {
+            let region = self.display.region();
+            let background = TextColor::DEFAULT_BACKGROUND.rgb();
+            self.display.fill(region.xs(), region.ys(), background);
+            self.clear_pending = false;
+        }
+        for pane in &mut self.panes[self.active] {
+            pane.render(&mut self.display);
+        }
+        self.display.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bootloader_api::info::PixelFormat;
+    use core::fmt::Write;
+
+    fn console(width: usize, height: usize) -> Console {
+        let byte_len = width * height * 4;
+        let info = FrameBufferInfo {
+            byte_len,
+            width,
+            height,
+            pixel_format: PixelFormat::Rgb,
+            bytes_per_pixel: 4,
+            stride: width,
+        };
+        Console::new(vec![0; byte_len].leak(), info)
+    }
+
+    #[test]
+    fn status_bar_takes_the_top_line() {
+        let mut console = console(200, 150);
+        let pane = console.add_status_bar(1).unwrap();
+        assert_eq!(pane, 1);
+
+        let bar = console.pane(1, pane).unwrap();
+        assert_eq!(bar.grid().rows(), 1);
+        let bar_height = bar.region().height;
+        let terminal = console.terminal(1).unwrap();
+        assert_eq!(terminal.region(), Region::new(0, bar_height, 200, 150 - bar_height));
+
+        // The other consoles keep the whole display.
+        assert_eq!(console.terminal(0).unwrap().region(), console.region());
+    }
+
+    #[test]
+    fn terminal_keeps_its_text_below_the_status_bar() {
+        let mut console = console(200, 150);
+        let terminal = console.terminal(1).unwrap();
+        terminal.set_serial_echo(false);
+        let rows = terminal.grid().rows();
+        for line in 0..rows {
+            writeln!(terminal, "{}", line).unwrap();
+        }
+        console.add_status_bar(1).unwrap();
+        // The grid lost a row, and with it the oldest line.
+        let terminal = console.terminal(1).unwrap();
+        assert_eq!(terminal.grid().rows(), rows - 1);
+        assert_eq!(terminal.grid().row_text(0), "2");
+        assert_eq!(terminal.cursor_position(), (0, rows - 2));
+    }
+}
@@ -0,0 +1,450 @@
+use alloc::collections::TryReserveError;
+use alloc::vec::Vec;
+use bootloader_api::info::{FrameBufferInfo, PixelFormat};
+use core::ops::Range;
+use core::ptr;
+
+/// A rectangle of pixels on the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    /// Creates a region from its top left corner and size.
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The columns of pixels covered.
+    pub fn xs(&self) -> Range<usize> {
+        self.x..self.x + self.width
+    }
+
+    /// The scanlines covered.
+    pub fn ys(&self) -> Range<usize> {
+        self.y..self.y + self.height
+    }
+
+    /// Splits off the top `height` scanlines, e.g. for a status bar, returning
+    /// them and the rest of the region.
+    pub fn split_top(&self, height: usize) -> (Region, Region) {
+        let height = height.min(self.height);
+        (
+            Region::new(self.x, self.y, self.width, height),
+            Region::new(self.x, self.y + height, self.width, self.height - height),
+        )
+    }
+
+    /// The part of this region that also lies within `other`.
+    pub fn intersect(&self, other: &Region) -> Region {
+        let (left, top) = (self.x.max(other.x), self.y.max(other.y));
+        let right = (self.x + self.width).min(other.x + other.width).max(left);
+        let bottom = (self.y + self.height).min(other.y + other.height).max(top);
+        Region::new(left, top, right - left, bottom - top)
+    }
+}
+
+/// The framebuffer, optionally drawn through a back buffer in RAM.
+pub struct Display {
+    framebuffer: &'static mut [u8],
+    /// Copy of the framebuffer in RAM that all drawing goes to, once enabled.
+    back_buffer: Option<Vec<u8>>,
+    /// Scanlines of the back buffer not yet copied to the framebuffer.
+    dirty: Option<Range<usize>>,
+    info: FrameBufferInfo,
+}
+
+impl Display {
+    /// Creates a display drawing directly to the given framebuffer.
+    pub fn new(framebuffer: &'static mut [u8], info: FrameBufferInfo) -> Self {
+        Display {
+            framebuffer,
+            back_buffer: None,
+            dirty: None,
+            info,
+        }
+    }
+
+    /// Retrieves the framebuffer width.
+    pub fn width(&self) -> usize {
+        self.info.width
+    }
+
+    /// Retrieves the framebuffer height.
+    pub fn height(&self) -> usize {
+        self.info.height
+    }
+
+    /// The whole display area.
+    pub fn region(&self) -> Region {
+        Region::new(0, 0, self.width(), self.height())
+    }
+
+    /// Moves all further drawing to a back buffer in RAM that `flush` copies out.
+    ///
+    /// Video memory is slow, in particular to read, so this makes scrolling much
+    /// cheaper. Requires the kernel heap; if it cannot hold a copy of the
+    /// framebuffer, drawing keeps going to video memory directly.
+    pub fn enable_back_buffer(&mut self) -> Result<(), TryReserveError> {
+        if self.back_buffer.is_none() {
+            let mut back_buffer = Vec::new();
+            back_buffer.try_reserve_exact(self.framebuffer.len())?;
+            back_buffer.extend_from_slice(self.framebuffer);
+            self.back_buffer = Some(back_buffer);
+        }
+        Ok(())
+    }
+
+    /// Whether drawing goes to a back buffer.
+    pub fn has_back_buffer(&self) -> bool {
+        self.back_buffer.is_some()
+    }
+
+    /// Copies the scanlines drawn since the last flush to the framebuffer.
+    pub fn flush(&mut self) {
+        let (Some(back_buffer), Some(dirty)) = (self.back_buffer.as_ref(), self.dirty.take())
+        else {
+            return;
+        };
+        let scanline_bytes = self.scanline_bytes();
+        let end = (dirty.end * scanline_bytes).min(self.framebuffer.len());
+        let start = (dirty.start * scanline_bytes).min(end);
+        self.framebuffer[start..end].copy_from_slice(&back_buffer[start..end]);
+    }
+
+    /// Bytes from one scanline to the next, which are `stride` pixels apart.
+    fn scanline_bytes(&self) -> usize {
+        self.info.stride * self.info.bytes_per_pixel
+    }
+
+    /// The buffer drawing goes to.
+    fn buffer(&mut self) -> &mut [u8] {
+        match self.back_buffer.as_mut() {
+            Some(back_buffer) => back_buffer,
+            None => self.framebuffer,
+        }
+    }
+
+    /// Records that the given scanlines of the back buffer changed.
+    fn mark_dirty(&mut self, scanlines: Range<usize>) {
+        if self.back_buffer.is_none() {
+            return;
+        }
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => dirty.start.min(scanlines.start)..dirty.end.max(scanlines.end),
+            None => scanlines,
+        });
+    }
+
+    /// Moves the pixels of a rectangle up by `distance` scanlines. The bottom
+    /// `distance` scanlines of the rectangle keep their old content.
+    pub fn scroll_up(&mut self, xs: Range<usize>, ys: Range<usize>, distance: usize) {
+        let (xs, ys) = (self.clip_x(xs), self.clip_y(ys));
+        if distance >= ys.len() {
+            return;
+        }
+        let full_width = xs.start == 0 && xs.end == self.width();
+        let scanline_bytes = self.scanline_bytes();
+        let bytes_per_pixel = self.info.bytes_per_pixel;
+        let buffer = self.buffer();
+        if full_width {
+            // Whole scanlines are contiguous and move in one go.
+            let end = (ys.end * scanline_bytes).min(buffer.len());
+            let from = (ys.start + distance) * scanline_bytes;
+            buffer.copy_within(from..end, ys.start * scanline_bytes);
+        } else {
+            let row_bytes = xs.len() * bytes_per_pixel;
+            for y in ys.start..ys.end - distance {
+                let to = y * scanline_bytes + xs.start * bytes_per_pixel;
+                let from = to + distance * scanline_bytes;
+                buffer.copy_within(from..from + row_bytes, to);
+            }
+        }
+        self.mark_dirty(ys);
+    }
+
+    /// Fills a rectangle of pixels with a color.
+    pub fn fill(&mut self, xs: Range<usize>, ys: Range<usize>, color: (u8, u8, u8)) {
+        for y in self.clip_y(ys) {
+            for x in self.clip_x(xs.clone()) {
+                self.write_pixel(x, y, color);
+            }
+        }
+    }
+
+    fn clip_x(&self, xs: Range<usize>) -> Range<usize> {
+        let end = xs.end.min(self.width());
+        xs.start.min(end)..end
+    }
+
+    fn clip_y(&self, ys: Range<usize>) -> Range<usize> {
+        let end = ys.end.min(self.height());
+        ys.start.min(end)..end
+    }
+
+    /// Writes a pixel of the given color at the specified position.
+    pub fn write_pixel(&mut self, x: usize, y: usize, (r, g, b): (u8, u8, u8)) {
+        let pixel_offset = y * self.info.stride + x;
+        let color = encode_pixel(self.info.pixel_format, (r, g, b));
+
+        // Formats never use more than four bytes; any extra bytes are padding.
+        let bytes_per_pixel = self.info.bytes_per_pixel;
+        let byte_offset = pixel_offset * bytes_per_pixel;
+        let color_bytes = bytes_per_pixel.min(color.len());
+        self.buffer()[byte_offset..(byte_offset + color_bytes)]
+            .copy_from_slice(&color[..color_bytes]);
+
+        if self.back_buffer.is_some() {
+            self.mark_dirty(y..y + 1);
+        } else {
+            unsafe {
+                ptr::read_volatile(&self.framebuffer[byte_offset]);
+            }
+        }
+    }
+
+    /// Reads back the color of a pixel, e.g. to blend another color over it.
+    ///
+    /// Without a back buffer this reads from video memory, which is slow.
+    pub fn read_pixel(&mut self, x: usize, y: usize) -> (u8, u8, u8) {
+        let bytes_per_pixel = self.info.bytes_per_pixel;
+        let byte_offset = (y * self.info.stride + x) * bytes_per_pixel;
+        let format = self.info.pixel_format;
+        let mut bytes = [0; 4];
+        let color_bytes = bytes_per_pixel.min(bytes.len());
+        bytes[..color_bytes]
+            .copy_from_slice(&self.buffer()[byte_offset..(byte_offset + color_bytes)]);
+        decode_pixel(format, bytes)
+    }
+}
+
+/// Encodes a color as the bytes of one pixel in the given format.
+fn encode_pixel(format: PixelFormat, (r, g, b): (u8, u8, u8)) -> [u8; 4] {
+    // Note: The alpha channel is set to 0xFF (opaque).
+    match format {
+        // For PixelFormat::Rgb, assume ordering: R, G, B, A.
+        PixelFormat::Rgb => [r, g, b, 0xFF],
+        // For PixelFormat::Bgr, assume ordering: B, G, R, A.
+        PixelFormat::Bgr => [b, g, r, 0xFF],
+        // Grayscale: a single luminance byte using the BT.601 weights.
+        PixelFormat::U8 => {
+            let luminance = (r as u32 * 77 + g as u32 * 150 + b as u32 * 29) >> 8;
+            [luminance as u8, 0, 0, 0]
+        }
+        // Each channel is an 8 bit field at the given bit offset of a little-endian pixel.
+        PixelFormat::Unknown {
+            red_position,
+            green_position,
+            blue_position,
+        } => {
+            let channel =
+                |value: u8, position: u8| (value as u32).checked_shl(position as u32).unwrap_or(0);
+            let pixel =
+                channel(r, red_position) | channel(g, green_position) | channel(b, blue_position);
+            pixel.to_le_bytes()
+        }
+        // Fallback for pixel formats added in the future.
+        _ => [r, g, b, 0xFF],
+    }
+}
+
+/// Decodes the bytes of one pixel in the given format; the reverse of `encode_pixel`.
+fn decode_pixel(format: PixelFormat, bytes: [u8; 4]) -> (u8, u8, u8) {
+    match format {
+        PixelFormat::Rgb => (bytes[0], bytes[1], bytes[2]),
+        PixelFormat::Bgr => (bytes[2], bytes[1], bytes[0]),
+        PixelFormat::U8 => (bytes[0], bytes[0], bytes[0]),
+        PixelFormat::Unknown {
+            red_position,
+            green_position,
+            blue_position,
+        } => {
+            let pixel = u32::from_le_bytes(bytes);
+            let channel = |position: u8| pixel.checked_shr(position as u32).unwrap_or(0) as u8;
+            (
+                channel(red_position),
+                channel(green_position),
+                channel(blue_position),
+            )
+        }
+        _ => (bytes[0], bytes[1], bytes[2]),
+    }
+}
+
+#[cfg(test)]
+impl Display {
+    /// A display over a zeroed buffer in RAM, with `stride - width` pixels of
+    /// padding after each scanline.
+    pub fn for_test(
+        pixel_format: PixelFormat,
+        bytes_per_pixel: usize,
+        width: usize,
+        height: usize,
+        stride: usize,
+    ) -> Self {
+        let byte_len = stride * height * bytes_per_pixel;
+        let framebuffer = alloc::vec![0; byte_len].leak();
+        let info = FrameBufferInfo {
+            byte_len,
+            width,
+            height,
+            pixel_format,
+            bytes_per_pixel,
+            stride,
+        };
+        Display::new(framebuffer, info)
+    }
+}
+
+/// The pixel formats displays are tested in, with their pixel sizes.
+#[cfg(test)]
+pub const TEST_FORMATS: [(PixelFormat, usize); 3] = [
+    (PixelFormat::Rgb, 4),
+    (PixelFormat::Bgr, 4),
+    (PixelFormat::U8, 1),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 5;
+    const HEIGHT: usize = 6;
+    const STRIDE: usize = 8;
+
+    /// A color unique to each pixel; gray, so it survives the U8 format.
+    fn pattern(x: usize, y: usize) -> (u8, u8, u8) {
+        let level = (y * WIDTH + x) as u8 * 7 + 1;
+        (level, level, level)
+    }
+
+    /// A display of the given format filled with `pattern`.
+    fn patterned(format: PixelFormat, bytes_per_pixel: usize) -> Display {
+        let mut display = Display::for_test(format, bytes_per_pixel, WIDTH, HEIGHT, STRIDE);
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                display.write_pixel(x, y, pattern(x, y));
+            }
+        }
+        display
+    }
+
+    #[test]
+    fn pixels_round_trip() {
+        for (format, bytes_per_pixel) in TEST_FORMATS {
+            let mut display = patterned(format, bytes_per_pixel);
+            for y in 0..HEIGHT {
+                for x in 0..WIDTH {
+                    assert_eq!(display.read_pixel(x, y), pattern(x, y), "{:?}", format);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn encodes_channels_in_format_order() {
+        let color = (0x10, 0x20, 0x30);
+        assert_eq!(encode_pixel(PixelFormat::Rgb, color), [0x10, 0x20, 0x30, 0xFF]);
+        assert_eq!(encode_pixel(PixelFormat::Bgr, color), [0x30, 0x20, 0x10, 0xFF]);
+        assert_eq!(decode_pixel(PixelFormat::Bgr, [0x30, 0x20, 0x10, 0xFF]), color);
+    }
+
+    #[test]
+    fn encodes_grayscale_by_luminance() {
+        // Each channel is weighted on its own: 255 * weight >> 8.
+        assert_eq!(encode_pixel(PixelFormat::U8, (255, 0, 0)), [76, 0, 0, 0]);
+        assert_eq!(encode_pixel(PixelFormat::U8, (0, 255, 0)), [149, 0, 0, 0]);
+        assert_eq!(encode_pixel(PixelFormat::U8, (0, 0, 255)), [28, 0, 0, 0]);
+        assert_eq!(decode_pixel(PixelFormat::U8, [149, 0, 0, 0]), (149, 149, 149));
+    }
+
+    #[test]
+    fn encodes_channels_at_their_bit_positions() {
+        let color = (0x10, 0x20, 0x30);
+        let xrgb = PixelFormat::Unknown {
+            red_position: 16,
+            green_position: 8,
+            blue_position: 0,
+        };
+        assert_eq!(encode_pixel(xrgb, color), [0x30, 0x20, 0x10, 0]);
+        assert_eq!(decode_pixel(xrgb, [0x30, 0x20, 0x10, 0]), color);
+
+        let rgbx = PixelFormat::Unknown {
+            red_position: 24,
+            green_position: 16,
+            blue_position: 8,
+        };
+        assert_eq!(encode_pixel(rgbx, color), [0, 0x30, 0x20, 0x10]);
+        assert_eq!(decode_pixel(rgbx, [0, 0x30, 0x20, 0x10]), color);
+    }
+
+    #[test]
+    fn scroll_up_moves_whole_scanlines() {
+        for (format, bytes_per_pixel) in TEST_FORMATS {
+            let mut display = patterned(format, bytes_per_pixel);
+            display.scroll_up(0..WIDTH, 1..HEIGHT, 2);
+            for x in 0..WIDTH {
+                assert_eq!(display.read_pixel(x, 0), pattern(x, 0), "{:?}", format);
+                for y in 1..HEIGHT - 2 {
+                    assert_eq!(display.read_pixel(x, y), pattern(x, y + 2), "{:?}", format);
+                }
+                // The bottom scanlines keep their content.
+                for y in HEIGHT - 2..HEIGHT {
+                    assert_eq!(display.read_pixel(x, y), pattern(x, y), "{:?}", format);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn scroll_up_moves_part_of_scanlines() {
+        for (format, bytes_per_pixel) in TEST_FORMATS {
+            let mut display = patterned(format, bytes_per_pixel);
+            display.scroll_up(1..3, 0..HEIGHT, 1);
+            for y in 0..HEIGHT {
+                for x in 0..WIDTH {
+                    let source_y = if (1..3).contains(&x) && y < HEIGHT - 1 {
+                        y + 1
+                    } else {
+                        y
+                    };
+                    assert_eq!(display.read_pixel(x, y), pattern(x, source_y), "{:?}", format);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn scroll_up_clips_to_the_display() {
+        let mut display = patterned(PixelFormat::Rgb, 4);
+        display.scroll_up(0..WIDTH + 10, HEIGHT - 2..HEIGHT + 10, 1);
+        for x in 0..WIDTH {
+            assert_eq!(display.read_pixel(x, HEIGHT - 2), pattern(x, HEIGHT - 1));
+        }
+        // Scrolling by the whole height leaves the pixels alone.
+        let mut display = patterned(PixelFormat::Rgb, 4);
+        display.scroll_up(0..WIDTH, 0..HEIGHT, HEIGHT);
+        assert_eq!(display.read_pixel(0, 0), pattern(0, 0));
+    }
+
+    #[test]
+    fn scroll_up_in_back_buffer_is_flushed() {
+        for (format, bytes_per_pixel) in TEST_FORMATS {
+            let mut display = patterned(format, bytes_per_pixel);
+            display.enable_back_buffer().unwrap();
+            display.scroll_up(0..WIDTH, 0..HEIGHT, 3);
+            assert_ne!(display.framebuffer, &display.back_buffer.as_ref().unwrap()[..]);
+            display.flush();
+            assert_eq!(display.framebuffer, &display.back_buffer.as_ref().unwrap()[..]);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Parser for the console's escape markup.
+//!
+//! Supported sequences:
+//! - `\n` starts a new line and `\t` advances to the next tab stop.
+//! - `\c<color>` switches the text color, where `<color>` is an alphabetic color
+//!   name such as `Blue` (case-insensitive) or a hex code such as `#ff8000`.
+//! - `\cReset` switches back to the default text color.
+//! - `\b<color>` switches the background color in the same way, and `\bReset`
+//!   switches back to the default background.
+//! - `\\` writes a literal backslash.
+//!
+//! Malformed sequences are never dropped: an unknown escape, an unknown color
+//! name or a trailing backslash is passed through as literal text.
+
+use super::TextColor;
+
+/// Name that resets the text color when used with `\c` or `\b`.
+const RESET_NAME: &str = "reset";
+
+/// A piece of console markup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// Text written as is.
+    Text(&'a str),
+    /// `\n`
+    Newline,
+    /// `\t`
+    Tab,
+    /// `\c<color>`
+    Color(TextColor),
+    /// `\cReset`
+    Reset,
+    /// `\b<color>`
+    Background(TextColor),
+    /// `\bReset`
+    ResetBackground,
+}
+
+/// Splits markup into tokens.
+pub struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    /// Creates a parser over the given markup.
+    pub fn new(markup: &'a str) -> Self {
+        Parser { rest: markup }
+    }
+
+    /// Parses the escape at the start of `rest`, returning it and its length in bytes.
+    fn escape(&self) -> (Token<'a>, usize) {
+        let rest = self.rest;
+        match rest[1..].chars().next() {
+            Some('n') => (Token::Newline, 2),
+            Some('t') => (Token::Tab, 2),
+            // The escaped backslash is the second byte of the sequence.
+            Some('\\') => (Token::Text(&rest[1..2]), 2),
+            Some('c') => self.color_escape(Token::Color, Token::Reset),
+            Some('b') => self.color_escape(Token::Background, Token::ResetBackground),
+            // Unknown escapes are kept, including the character after the backslash.
+            Some(other) => {
+                let len = 1 + other.len_utf8();
+                (Token::Text(&rest[..len]), len)
+            }
+            // A trailing backslash is kept as well.
+            None => (Token::Text(rest), 1),
+        }
+    }
+
+    /// Parses a `\c` or `\b` escape at the start of `rest` into a color token
+    /// or, for the reset name, the reset token.
+    fn color_escape(
+        &self,
+        color_token: fn(TextColor) -> Token<'a>,
+        reset_token: Token<'a>,
+    ) -> (Token<'a>, usize) {
+        let rest = self.rest;
+        // A name is alphabetic, a hex code is `#` followed by hex digits.
+        let name_len = match rest[2..].strip_prefix('#') {
+            Some(hex) => {
+                let digits = hex.find(|c: char| !c.is_ascii_hexdigit()).unwrap_or(hex.len());
+                1 + digits.min(6)
+            }
+            None => rest[2..]
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(rest.len() - 2),
+        };
+        let name = &rest[2..2 + name_len];
+        let len = 2 + name_len;
+        if name.eq_ignore_ascii_case(RESET_NAME) {
+            (reset_token, len)
+        } else if let Some(color) = TextColor::from_str(name) {
+            (color_token(color), len)
+        } else {
+            (Token::Text(&rest[..len]), len)
+        }
+    }
+}
+
+impl<'a> Iterator for Parser<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let (token, len) = match self.rest.find('\\') {
+            Some(0) => self.escape(),
+            Some(end) => (Token::Text(&self.rest[..end]), end),
+            None => (Token::Text(self.rest), self.rest.len()),
+        };
+        self.rest = &self.rest[len..];
+        Some(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(markup: &str) -> Vec<Token<'_>> {
+        Parser::new(markup).collect()
+    }
+
+    #[test]
+    fn trailing_backslash_is_text() {
+        assert_eq!(tokens(r"abc\"), [Token::Text("abc"), Token::Text(r"\")]);
+    }
+
+    #[test]
+    fn unknown_escape_is_text() {
+        assert_eq!(
+            tokens(r"a\qb\é"),
+            [
+                Token::Text("a"),
+                Token::Text(r"\q"),
+                Token::Text("b"),
+                Token::Text(r"\é"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_color_name_is_text() {
+        assert_eq!(
+            tokens(r"\cMauve text"),
+            [Token::Text(r"\cMauve"), Token::Text(" text")]
+        );
+    }
+
+    #[test]
+    fn short_hex_color_is_text() {
+        assert_eq!(
+            tokens(r"\c#ff text"),
+            [Token::Text(r"\c#ff"), Token::Text(" text")]
+        );
+    }
+
+    #[test]
+    fn hex_color_takes_six_digits() {
+        assert_eq!(
+            tokens(r"\c#ff8000ab"),
+            [Token::Color(TextColor::Rgb(0xFF, 0x80, 0x00)), Token::Text("ab")]
+        );
+    }
+
+    #[test]
+    fn escaped_backslash_is_one_backslash() {
+        assert_eq!(
+            tokens(r"a\\nb"),
+            [Token::Text("a"), Token::Text(r"\"), Token::Text("nb")]
+        );
+    }
+
+    #[test]
+    fn reset_and_names_ignore_case() {
+        assert_eq!(
+            tokens(r"\cblue\tx\cReset\n\cRESET"),
+            [
+                Token::Color(TextColor::Blue),
+                Token::Tab,
+                Token::Text("x"),
+                Token::Reset,
+                Token::Newline,
+                Token::Reset,
+            ]
+        );
+    }
+
+    #[test]
+    fn background_escapes() {
+        assert_eq!(
+            tokens(r"\bDarkBlue x\bReset\bMauve"),
+            [
+                Token::Background(TextColor::DarkBlue),
+                Token::Text(" x"),
+                Token::ResetBackground,
+                Token::Text(r"\bMauve"),
+            ]
+        );
+    }
+}
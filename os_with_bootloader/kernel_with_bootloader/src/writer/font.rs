@@ -0,0 +1,74 @@
+use crate::writer::constants::font_constants::{
+    BACKUP_CHAR, BOLD_FONT_WEIGHT, CHAR_RASTER_HEIGHT, FONT_WEIGHT, LINE_SPACING,
+};
+use noto_sans_mono_bitmap::{get_raster, get_raster_width, FontWeight, RasterHeight, RasterizedChar};
+
+/// The size and weight of the console font.
+#[derive(Debug, Clone, Copy)]
+pub struct FontConfig {
+    pub height: RasterHeight,
+    /// Weight of regular text; bold text always uses `BOLD_FONT_WEIGHT`.
+    pub weight: FontWeight,
+}
+
+/// Fonts selected by SGR 11 to 17, the ANSI "alternative font" codes.
+const ALTERNATIVE_FONTS: [FontConfig; 7] = [
+    FontConfig::new(RasterHeight::Size20, FontWeight::Regular),
+    FontConfig::new(RasterHeight::Size24, FontWeight::Regular),
+    FontConfig::new(RasterHeight::Size32, FontWeight::Regular),
+    FontConfig::new(RasterHeight::Size16, FontWeight::Light),
+    FontConfig::new(RasterHeight::Size20, FontWeight::Light),
+    FontConfig::new(RasterHeight::Size24, FontWeight::Light),
+    FontConfig::new(RasterHeight::Size32, FontWeight::Light),
+];
+
+// The font crate's enums do not implement `PartialEq`, so compare their values.
+impl PartialEq for FontConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.height.val() == other.height.val() && self.weight.val() == other.weight.val()
+    }
+}
+
+impl Eq for FontConfig {}
+
+impl FontConfig {
+    /// The font the console starts with.
+    pub const DEFAULT: FontConfig = FontConfig::new(CHAR_RASTER_HEIGHT, FONT_WEIGHT);
+
+    /// Creates a font configuration.
+    pub const fn new(height: RasterHeight, weight: FontWeight) -> Self {
+        FontConfig { height, weight }
+    }
+
+    /// Looks up the font selected by an SGR font code: 10 is the default font
+    /// and 11 to 17 are alternatives.
+    pub fn from_sgr(code: u16) -> Option<Self> {
+        match code {
+            10 => Some(FontConfig::DEFAULT),
+            11..=17 => Some(ALTERNATIVE_FONTS[(code - 11) as usize]),
+            _ => None,
+        }
+    }
+
+    /// Width of a character cell.
+    pub fn char_width(&self) -> usize {
+        get_raster_width(self.weight, self.height)
+    }
+
+    /// Height of a glyph.
+    pub fn char_height(&self) -> usize {
+        self.height.val()
+    }
+
+    /// Distance between the tops of two lines of text.
+    pub fn line_height(&self) -> usize {
+        self.char_height() + LINE_SPACING
+    }
+
+    /// Retrieves the raster of the given char or a backup char.
+    pub fn raster(&self, c: char, bold: bool) -> RasterizedChar {
+        let weight = if bold { BOLD_FONT_WEIGHT } else { self.weight };
+        let get = |c: char| get_raster(c, weight, self.height);
+        get(c).unwrap_or_else(|| get(BACKUP_CHAR).expect("Failed to load backup char raster"))
+    }
+}
@@ -0,0 +1,154 @@
+use crate::writer::TextColor;
+#[cfg(test)]
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// How the character of a cell is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attributes {
+    pub color: TextColor,
+    pub background: TextColor,
+    pub bold: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+
+impl Default for Attributes {
+    fn default() -> Self {
+        Attributes {
+            color: TextColor::default(),
+            background: TextColor::DEFAULT_BACKGROUND,
+            bold: false,
+            underline: false,
+            reverse: false,
+        }
+    }
+}
+
+/// A character together with its attributes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub attributes: Attributes,
+}
+
+impl Cell {
+    /// An empty cell showing only the background of the given attributes.
+    pub fn blank(attributes: Attributes) -> Self {
+        Cell {
+            ch: ' ',
+            attributes,
+        }
+    }
+}
+
+/// The characters on screen, as rows of cells.
+#[derive(Clone)]
+pub struct TextGrid {
+    columns: usize,
+    rows: usize,
+    cells: Vec<Cell>,
+}
+
+impl TextGrid {
+    /// Creates a grid filled with blank cells.
+    pub fn new(columns: usize, rows: usize, blank: Cell) -> Self {
+        TextGrid {
+            columns,
+            rows,
+            cells: vec![blank; columns * rows],
+        }
+    }
+
+    /// Number of cells per row.
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// Number of rows.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The cell at the given position, if it is on the grid.
+    pub fn cell(&self, column: usize, row: usize) -> Option<&Cell> {
+        if column < self.columns && row < self.rows {
+            self.cells.get(row * self.columns + column)
+        } else {
+            None
+        }
+    }
+
+    /// Replaces the cell at the given position; positions off the grid are ignored.
+    pub fn set(&mut self, column: usize, row: usize, cell: Cell) {
+        if column < self.columns && row < self.rows {
+            self.cells[row * self.columns + column] = cell;
+        }
+    }
+
+    /// The cells of a row.
+    pub fn row(&self, row: usize) -> &[Cell] {
+        let start = row * self.columns;
+        &self.cells[start..start + self.columns]
+    }
+
+    /// The text of a row without trailing blanks.
+    #[cfg(test)]
+    pub fn row_text(&self, row: usize) -> String {
+        let mut text: String = self.row(row).iter().map(|cell| cell.ch).collect();
+        text.truncate(text.trim_end().len());
+        text
+    }
+
+    /// Replaces a row with the given cells, cutting them off at the grid's width
+    /// and blanking the columns after them.
+    pub fn set_row(&mut self, row: usize, cells: &[Cell], blank: Cell) {
+        if row >= self.rows {
+            return;
+        }
+        let kept = cells.len().min(self.columns);
+        let start = row * self.columns;
+        self.cells[start..start + kept].copy_from_slice(&cells[..kept]);
+        self.clear_cells(row, kept..self.columns, blank);
+    }
+
+    /// Blanks the given columns of a row.
+    pub fn clear_cells(&mut self, row: usize, columns: Range<usize>, blank: Cell) {
+        if row >= self.rows {
+            return;
+        }
+        let start = row * self.columns;
+        let end = columns.end.min(self.columns);
+        let begin = columns.start.min(end);
+        self.cells[start + begin..start + end].fill(blank);
+    }
+
+    /// Blanks every cell.
+    pub fn clear(&mut self, blank: Cell) {
+        self.cells.fill(blank);
+    }
+
+    /// Changes the size of the grid, keeping the cells at the top left and
+    /// blanking new ones.
+    pub fn resize(&mut self, columns: usize, rows: usize, blank: Cell) {
+        let mut resized = TextGrid::new(columns, rows, blank);
+        let kept_columns = columns.min(self.columns);
+        for row in 0..rows.min(self.rows) {
+            let start = row * columns;
+            resized.cells[start..start + kept_columns]
+                .copy_from_slice(&self.row(row)[..kept_columns]);
+        }
+        *self = resized;
+    }
+
+    /// Moves every row up by one, dropping the first and blanking the last.
+    pub fn scroll_up(&mut self, blank: Cell) {
+        if self.rows == 0 {
+            return;
+        }
+        self.cells.copy_within(self.columns.., 0);
+        self.clear_cells(self.rows - 1, 0..self.columns, blank);
+    }
+}
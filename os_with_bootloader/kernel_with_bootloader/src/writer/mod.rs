@@ -1,58 +1,167 @@
+// `writer::writer` predates the split of the writer into submodules.
+#[allow(clippy::module_inception)]
 pub mod writer;
+pub mod ansi;
+pub mod color;
+pub mod console;
 pub mod constants;
+pub mod display;
+pub mod escape;
+pub mod font;
+pub mod grid;
+pub mod scrollback;
 
-pub use writer::{FrameBufferWriter, BORDER_PADDING, TextColor};
+pub use color::TextColor;
+pub use console::{Console, CONSOLE_COUNT};
+pub use display::Region;
+pub use writer::{FrameBufferWriter, BORDER_PADDING};
 
-#[macro_export]
-macro_rules! print {
-    ($writer:expr, $text:expr) => {{
-        use core::fmt::Write;
-        let mut text = $text;
-        
-        // Handle escape sequences
-        while let Some(pos) = text.find('\\') {
-            let (before, rest) = text.split_at(pos);
-            let mut rest_iter = rest.chars();
-            rest_iter.next(); // Skip '\'
-
-            match rest_iter.next() {
-                Some('n') => {
-                    write!($writer, "{}", before).unwrap();
-                    $writer.newline();
-                    text = rest_iter.as_str();
-                }
-                Some('t') => {
-                    write!($writer, "{}", before).unwrap();
-                    $writer.write_tab();
-                    text = rest_iter.as_str();
-                }
-                Some('c') => {
-                    if let Some(end_pos) = rest_iter.as_str().find(' ') {
-                        let (color_name, remaining) = rest_iter.as_str().split_at(end_pos);
-                        if let Some(color) = TextColor::from_str(color_name) {
-                            $writer.set_color(color);
-                        }
-                        text = remaining;
-                    } else {
-                        text = "";
-                    }
-                }
-                _ => {
-                    write!($writer, "{}\\", before).unwrap();
-                    text = rest_iter.as_str();
-                }
+use bootloader_api::info::FrameBufferInfo;
+use crate::timer;
+use core::fmt;
+use x86_64::instructions::interrupts;
+
+/// The console `print!` writes to, shown with Alt+F1.
+pub const MAIN_CONSOLE: usize = 0;
+
+/// The console log records are written to, shown with Alt+F2.
+pub const LOG_CONSOLE: usize = 1;
+
+/// The global virtual consoles, installed once by `init`.
+///
+/// Only lock it through `with_console` (or with interrupts disabled), so an
+/// interrupt handler printing to the console can never spin on a lock held by
+/// the code it interrupted.
+pub static CONSOLE: spin::Mutex<Option<Console>> = spin::Mutex::new(None);
+
+/// Installs the global consoles on the given framebuffer.
+pub fn init(framebuffer: &'static mut [u8], info: FrameBufferInfo) {
+    interrupts::without_interrupts(|| {
+        let mut console = CONSOLE.lock();
+        assert!(console.is_none(), "console already initialized");
+        *console = Some(Console::new(framebuffer, info));
+    });
+}
+
+/// Runs `f` on the global consoles with interrupts disabled, then flushes the
+/// output of the one on screen.
+///
+/// Returns `None` if the console has not been initialized.
+pub fn with_console<R>(f: impl FnOnce(&mut Console) -> R) -> Option<R> {
+    interrupts::without_interrupts(|| {
+        CONSOLE.lock().as_mut().map(|console| {
+            let result = f(console);
+            console.flush();
+            result
+        })
+    })
+}
+
+/// Runs `f` on the terminal of a virtual console; see `with_console`.
+pub fn with_terminal<R>(index: usize, f: impl FnOnce(&mut FrameBufferWriter) -> R) -> Option<R> {
+    with_console(|console| console.terminal(index).map(f)).flatten()
+}
+
+/// Runs `f` on a pane of a virtual console; see `with_console`.
+pub fn with_pane<R>(
+    index: usize,
+    pane: usize,
+    f: impl FnOnce(&mut FrameBufferWriter) -> R,
+) -> Option<R> {
+    with_console(|console| console.pane(index, pane).map(f)).flatten()
+}
+
+/// Runs `f` on the terminal of the main console; see `with_console`.
+pub fn with_writer<R>(f: impl FnOnce(&mut FrameBufferWriter) -> R) -> Option<R> {
+    with_terminal(MAIN_CONSOLE, f)
+}
+
+/// Puts a virtual console on screen.
+pub fn switch_console(index: usize) {
+    with_console(|console| console.switch_to(index));
+}
+
+/// How long a blinking cursor stays in each phase.
+const CURSOR_BLINK_INTERVAL_MS: u64 = 500;
+
+/// Blinks the cursor at its interval; called on every timer interrupt.
+pub(crate) fn on_timer_tick(ticks: u64) {
+    let interval = CURSOR_BLINK_INTERVAL_MS * timer::TIMER_HZ as u64 / 1000;
+    if !ticks.is_multiple_of(interval) {
+        return;
+    }
+    // Skip this blink rather than wait for a console that is in use.
+    let Some(mut console) = CONSOLE.try_lock() else {
+        return;
+    };
+    if let Some(console) = console.as_mut() {
+        console.blink();
+        console.flush();
+    }
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    let text = alloc::fmt::format(args);
+    let printed = with_writer(|writer| write_markup(writer, &text));
+    if printed.is_none() {
+        // Without a framebuffer, serial is the only console there is.
+        interrupts::without_interrupts(|| {
+            let _ = write_markup(&mut *crate::serial::SERIAL1.lock(), &text);
+        });
+    }
+}
+
+/// A console that `write_markup` can render to.
+pub trait MarkupTarget: fmt::Write {
+    /// Changes the text color, if the target has colors at all.
+    fn set_color(&mut self, _color: TextColor) {}
+
+    /// Changes the background color, if the target has colors at all.
+    fn set_background(&mut self, _background: TextColor) {}
+}
+
+impl MarkupTarget for FrameBufferWriter {
+    fn set_color(&mut self, color: TextColor) {
+        FrameBufferWriter::set_color(self, color);
+    }
+
+    fn set_background(&mut self, background: TextColor) {
+        FrameBufferWriter::set_background(self, background);
+    }
+}
+
+impl MarkupTarget for crate::serial::SerialPort {}
+
+/// Renders console markup (see [`escape`]) to the target.
+pub fn write_markup(target: &mut impl MarkupTarget, markup: &str) -> fmt::Result {
+    for token in escape::Parser::new(markup) {
+        match token {
+            escape::Token::Text(text) => target.write_str(text)?,
+            escape::Token::Newline => target.write_char('\n')?,
+            escape::Token::Tab => target.write_char('\t')?,
+            escape::Token::Color(color) => target.set_color(color),
+            escape::Token::Reset => target.set_color(TextColor::default()),
+            escape::Token::Background(background) => target.set_background(background),
+            escape::Token::ResetBackground => {
+                target.set_background(TextColor::DEFAULT_BACKGROUND)
             }
         }
+    }
+    Ok(())
+}
 
-        // Write the remaining text
-        write!($writer, "{}", text).unwrap();
-    }};
+/// Prints console markup to the main framebuffer console.
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => {
+        $crate::writer::_print(format_args!($($arg)*))
+    };
 }
 
+/// Prints console markup to the main framebuffer console, appending a newline.
 #[macro_export]
 macro_rules! println {
-    ($writer:expr, $text:expr) => {{
-        print!($writer, $text);
-        $writer.newline();
-    }};
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
 }
@@ -0,0 +1,109 @@
+use crate::writer::grid::{Attributes, Cell, TextGrid};
+
+/// Number of lines kept after they scroll off the top of a terminal.
+pub const SCROLLBACK_LINES: usize = 500;
+
+/// The lines that scrolled off the top of a terminal, oldest first.
+///
+/// The lines are kept in a ring of rows allocated up front, so scrolling never
+/// allocates. Once full, the oldest line is dropped for every new one.
+pub struct Scrollback {
+    lines: TextGrid,
+    /// Row of `lines` holding the oldest line.
+    first: usize,
+    line_count: usize,
+}
+
+impl Scrollback {
+    /// Creates an empty scrollback for lines of the given width.
+    pub fn new(columns: usize) -> Self {
+        Scrollback {
+            lines: TextGrid::new(columns, SCROLLBACK_LINES, Self::blank()),
+            first: 0,
+            line_count: 0,
+        }
+    }
+
+    /// The cell shown past the end of a line.
+    fn blank() -> Cell {
+        Cell::blank(Attributes::default())
+    }
+
+    /// Number of lines kept.
+    pub fn line_count(&self) -> usize {
+        self.line_count
+    }
+
+    /// Appends a line that scrolled off the screen, cut off at the scrollback's
+    /// width.
+    pub fn push(&mut self, row: &[Cell]) {
+        let slot = (self.first + self.line_count) % SCROLLBACK_LINES;
+        if self.line_count == SCROLLBACK_LINES {
+            self.first = (self.first + 1) % SCROLLBACK_LINES;
+        } else {
+            self.line_count += 1;
+        }
+        self.lines.set_row(slot, row, Self::blank());
+    }
+
+    /// The cell at the given column of a line, counted from the oldest one.
+    pub fn cell(&self, line: usize, column: usize) -> Cell {
+        if line >= self.line_count {
+            return Self::blank();
+        }
+        let slot = (self.first + line) % SCROLLBACK_LINES;
+        self.lines
+            .cell(column, slot)
+            .copied()
+            .unwrap_or(Self::blank())
+    }
+
+    /// Changes the width of the lines, keeping their start.
+    pub fn resize(&mut self, columns: usize) {
+        self.lines.resize(columns, SCROLLBACK_LINES, Self::blank());
+    }
+
+    /// Drops every line.
+    pub fn clear(&mut self) {
+        self.first = 0;
+        self.line_count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A line of the given width showing `ch` in every cell.
+    fn line(ch: char, columns: usize) -> alloc::vec::Vec<Cell> {
+        alloc::vec![Cell { ch, ..Scrollback::blank() }; columns]
+    }
+
+    #[test]
+    fn oldest_lines_are_dropped_once_full() {
+        let mut scrollback = Scrollback::new(2);
+        for i in 0..SCROLLBACK_LINES + 3 {
+            let ch = char::from_digit((i % 10) as u32, 10).unwrap();
+            scrollback.push(&line(ch, 2));
+        }
+        assert_eq!(scrollback.line_count(), SCROLLBACK_LINES);
+        assert_eq!(scrollback.cell(0, 1).ch, '3');
+        assert_eq!(scrollback.cell(SCROLLBACK_LINES - 1, 0).ch, '2');
+        assert_eq!(scrollback.cell(SCROLLBACK_LINES, 0), Scrollback::blank());
+    }
+
+    #[test]
+    fn lines_are_fitted_to_the_width() {
+        let mut scrollback = Scrollback::new(3);
+        scrollback.push(&line('a', 5));
+        scrollback.push(&line('b', 1));
+        assert_eq!(scrollback.cell(0, 2).ch, 'a');
+        assert_eq!(scrollback.cell(0, 3), Scrollback::blank());
+        assert_eq!(scrollback.cell(1, 0).ch, 'b');
+        assert_eq!(scrollback.cell(1, 1), Scrollback::blank());
+
+        scrollback.resize(1);
+        assert_eq!(scrollback.cell(0, 0).ch, 'a');
+        assert_eq!(scrollback.cell(0, 1), Scrollback::blank());
+    }
+}
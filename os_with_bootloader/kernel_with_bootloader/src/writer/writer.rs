@@ -1,219 +1,691 @@
+use crate::serial;
+use crate::writer::ansi::{Action, AnsiParser, ControlSequence};
 use crate::writer::constants;
+use crate::writer::display::{Display, Region};
+use crate::writer::grid::{Attributes, Cell, TextGrid};
+use crate::writer::scrollback::Scrollback;
+use crate::writer::TextColor;
 
-use constants::font_constants;
-use constants::font_constants::{BACKUP_CHAR, CHAR_RASTER_HEIGHT, FONT_WEIGHT};
-use noto_sans_mono_bitmap::{get_raster, RasterizedChar};
-use bootloader_api::info::{FrameBufferInfo, PixelFormat};
-use core::{fmt, ptr};
+use crate::writer::font::FontConfig;
+use constants::font_constants::BACKSPACE;
+use core::fmt;
 
 /// Additional spacing configurations.
 pub const BORDER_PADDING: usize = 1;
 
-/// Supported text colors.
-#[derive(Copy, Clone)]
-pub enum TextColor {
-    White,
-    Red,
-    Green,
-    Blue,
-    Yellow,
+/// Shapes of the text cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorStyle {
+    /// The cell under the cursor is shown in reverse video.
+    Block,
+    /// A line is drawn below the cell under the cursor.
+    Underline,
 }
 
-impl TextColor {
-    /// Converts a string (case-insensitively) into a TextColor.
-    pub fn from_str(color: &str) -> Option<Self> {
-        if color.eq_ignore_ascii_case("white") {
-            Some(TextColor::White)
-        } else if color.eq_ignore_ascii_case("red") {
-            Some(TextColor::Red)
-        } else if color.eq_ignore_ascii_case("green") {
-            Some(TextColor::Green)
-        } else if color.eq_ignore_ascii_case("blue") {
-            Some(TextColor::Blue)
-        } else if color.eq_ignore_ascii_case("yellow") {
-            Some(TextColor::Yellow)
-        } else {
-            None
-        }
-    }
-}
-
-/// Retrieves the raster of the given char or a backup char.
-fn get_char_raster(c: char) -> RasterizedChar {
-    fn get(c: char) -> Option<RasterizedChar> {
-        get_raster(c, FONT_WEIGHT, CHAR_RASTER_HEIGHT)
-    }
-    get(c).unwrap_or_else(|| get(BACKUP_CHAR).expect("Failed to load backup char raster"))
+/// A text terminal drawn to a region of a pixel-based framebuffer.
+///
+/// Text goes into a grid of character cells first; `render` then draws the cells
+/// that differ from what is already on the display.
+pub struct FrameBufferWriter {
+    /// The screen area drawn to.
+    region: Region,
+    font: FontConfig,
+    /// The text on screen.
+    grid: TextGrid,
+    /// The lines that scrolled off the top of the grid.
+    scrollback: Scrollback,
+    /// Lines the view is scrolled back into the scrollback; 0 shows the grid.
+    view_offset: usize,
+    /// The cells as last drawn, including the cursor.
+    drawn: TextGrid,
+    /// Set when every cell has to be drawn again on the next render.
+    redraw_all: bool,
+    /// Lines scrolled since the last render.
+    pending_scroll: usize,
+    /// The cursor column; equal to the column count while a wrap is pending.
+    column: usize,
+    row: usize,
+    cursor_visible: bool,
+    cursor_style: CursorStyle,
+    cursor_blinking: bool,
+    /// Whether a blinking cursor is currently in its visible phase.
+    blink_on: bool,
+    attributes: Attributes,
+    ansi: AnsiParser,
+    saved_cursor: SavedCursor,
+    /// Whether output is copied to the serial port.
+    serial_echo: bool,
 }
 
-/// A writer for logging text to a pixel-based framebuffer.
-pub struct FrameBufferWriter {
-    framebuffer: &'static mut [u8],
-    info: FrameBufferInfo,
-    x_pos: usize,
-    y_pos: usize,
-    color: TextColor,
+/// Cursor state stored by `ESC 7` and restored by `ESC 8`.
+#[derive(Clone, Copy)]
+struct SavedCursor {
+    column: usize,
+    row: usize,
+    attributes: Attributes,
 }
 
 impl FrameBufferWriter {
-    /// Creates a new writer with the given framebuffer.
-    pub fn new(framebuffer: &'static mut [u8], info: FrameBufferInfo) -> Self {
+    /// Creates a terminal covering a region of the display.
+    ///
+    /// The text grid lives on the kernel heap, which must be initialized.
+    pub fn new(region: Region) -> Self {
+        let font = FontConfig::DEFAULT;
+        let (columns, rows) = grid_size(region.width, region.height, &font);
+        let blank = Cell::blank(Attributes::default());
         let mut writer = Self {
-            framebuffer,
-            info,
-            x_pos: BORDER_PADDING,
-            y_pos: BORDER_PADDING,
-            color: TextColor::White,
+            region,
+            font,
+            grid: TextGrid::new(columns, rows, blank),
+            scrollback: Scrollback::new(columns),
+            view_offset: 0,
+            drawn: TextGrid::new(columns, rows, blank),
+            redraw_all: true,
+            pending_scroll: 0,
+            column: 0,
+            row: 0,
+            cursor_visible: true,
+            cursor_style: CursorStyle::Block,
+            cursor_blinking: true,
+            blink_on: true,
+            attributes: Attributes::default(),
+            ansi: AnsiParser::new(),
+            saved_cursor: SavedCursor {
+                column: 0,
+                row: 0,
+                attributes: Attributes::default(),
+            },
+            serial_echo: true,
         };
         writer.clear();
         writer
     }
 
-    /// Dynamically sets the cursor position.
+    /// Dynamically sets the cursor position, in pixels from the top left of the region.
     pub fn set_cursor_position(&mut self, x: isize, y: isize) {
         if x < 0 || y < 0 {
-            self.x_pos = BORDER_PADDING;
-            self.y_pos = BORDER_PADDING;
+            self.move_to_cell(0, 0);
+        } else {
+            let column = (x as usize).saturating_sub(BORDER_PADDING) / self.font.char_width();
+            let row = (y as usize).saturating_sub(BORDER_PADDING) / self.font.line_height();
+            self.move_to_cell(column, row);
+        }
+    }
+
+    /// The text on screen.
+    #[cfg(test)]
+    pub fn grid(&self) -> &TextGrid {
+        &self.grid
+    }
+
+    /// The column and row of the cursor.
+    #[cfg(test)]
+    pub fn cursor_position(&self) -> (usize, usize) {
+        self.cursor_cell()
+    }
+
+    /// Shows or hides the cursor.
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor_visible = visible;
+    }
+
+    /// Changes the shape of the cursor.
+    pub fn set_cursor_style(&mut self, style: CursorStyle) {
+        self.cursor_style = style;
+    }
+
+    /// Makes the cursor blink or stay on.
+    pub fn set_cursor_blinking(&mut self, blinking: bool) {
+        self.cursor_blinking = blinking;
+        self.blink_on = true;
+    }
+
+    /// Switches a blinking cursor between its visible and hidden phase.
+    pub fn blink(&mut self) {
+        if self.cursor_blinking {
+            self.blink_on = !self.blink_on;
+        }
+    }
+
+    /// Switches to another font, resizing the grid to the number of cells that
+    /// fit with it. The line with the cursor is kept on screen.
+    pub fn set_font(&mut self, font: FontConfig) {
+        if font != self.font {
+            self.font = font;
+            self.resize_grid();
+        }
+    }
+
+    /// The screen area drawn to.
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Moves the terminal to another screen area, resizing the grid to the
+    /// number of cells that fit. The line with the cursor is kept on screen.
+    ///
+    /// The region must lie within the display; the pixels of the old one are
+    /// left as they are.
+    pub fn set_region(&mut self, region: Region) {
+        if region != self.region {
+            self.region = region;
+            self.resize_grid();
+        }
+    }
+
+    /// Fits the grid to the region and font, scrolling up as far as needed to
+    /// keep the cursor on it.
+    fn resize_grid(&mut self) {
+        let (columns, rows) = grid_size(self.region.width, self.region.height, &self.font);
+        let blank = self.blank();
+        let overflow = (self.row + 1).saturating_sub(rows);
+        for _ in 0..overflow {
+            self.scroll_grid();
+        }
+        self.row -= overflow;
+        self.column = self.column.min(columns);
+        self.grid.resize(columns, rows, blank);
+        self.scrollback.resize(columns);
+        self.drawn = TextGrid::new(columns, rows, blank);
+        self.redraw_all = true;
+    }
+
+    /// Draws the whole screen again on the next render, e.g. after its pixels were
+    /// overwritten by something else.
+    pub fn redraw(&mut self) {
+        self.redraw_all = true;
+    }
+
+    /// Copies output to the serial port or stops doing so, e.g. for a status
+    /// bar that would otherwise clutter the serial log.
+    pub fn set_serial_echo(&mut self, echo: bool) {
+        self.serial_echo = echo;
+    }
+
+    /// Copies a character to the serial port, if enabled.
+    fn echo(&self, c: char) {
+        if self.serial_echo {
+            serial::tee_char(c);
+        }
+    }
+
+    /// Draws every cell that differs from what is on the display.
+    pub fn render(&mut self, display: &mut Display) {
+        if self.redraw_all {
+            // Also repaint the margins around the grid.
+            let background = self.attributes.background.rgb();
+            display.fill(self.region.xs(), self.region.ys(), background);
+        } else if self.pending_scroll > 0 && display.has_back_buffer() {
+            self.scroll_drawn(display);
+        }
+        self.pending_scroll = 0;
+        // While scrolled back, the top rows show the end of the scrollback.
+        let offset = self.view_offset.min(self.scrollback.line_count());
+        let first_line = self.scrollback.line_count() - offset;
+        let cursor_shown = self.cursor_visible && self.blink_on && offset == 0;
+        let cursor = cursor_shown.then_some((self.column, self.row));
+        for row in 0..self.grid.rows() {
+            for column in 0..self.grid.columns() {
+                let mut cell = match row.checked_sub(offset) {
+                    Some(grid_row) => self.grid.row(grid_row)[column],
+                    None => self.scrollback.cell(first_line + row, column),
+                };
+                // The cursor is drawn by flipping an attribute of the cell under it,
+                // so it is erased like any other change once the cell is drawn again.
+                if cursor == Some((column, row)) {
+                    match self.cursor_style {
+                        CursorStyle::Block => cell.attributes.reverse = !cell.attributes.reverse,
+                        CursorStyle::Underline => {
+                            cell.attributes.underline = !cell.attributes.underline
+                        }
+                    }
+                }
+                if self.redraw_all || self.drawn.cell(column, row) != Some(&cell) {
+                    self.render_cell(display, column, row, cell);
+                    self.drawn.set(column, row, cell);
+                }
+            }
+        }
+        self.redraw_all = false;
+    }
+
+    /// Draws a cell, including the line spacing below it.
+    fn render_cell(&self, display: &mut Display, column: usize, row: usize, cell: Cell) {
+        let (char_width, line_height) = (self.font.char_width(), self.font.line_height());
+        let (left, top) = (
+            BORDER_PADDING + column * char_width,
+            BORDER_PADDING + row * line_height,
+        );
+        let Attributes {
+            color,
+            background,
+            bold,
+            underline,
+            reverse,
+        } = cell.attributes;
+        let (foreground, glyph_background) = if reverse {
+            (background, color)
         } else {
-            self.x_pos = (x as usize).min(self.width().saturating_sub(BORDER_PADDING));
-            self.y_pos = (y as usize).min(self.height().saturating_sub(BORDER_PADDING));
+            (color, background)
+        };
+        let rendered_char = self.font.raster(cell.ch, bold);
+        let raster = rendered_char.raster();
+
+        // Bold glyphs may be wider, but the text stays on the regular cell grid.
+        for y in 0..line_height.min(self.height().saturating_sub(top)) {
+            for x in 0..char_width.min(self.width().saturating_sub(left)) {
+                let color = match raster.get(y) {
+                    // The anti-aliased intensity is the glyph's coverage of the pixel.
+                    Some(glyph_row) => {
+                        let intensity = glyph_row.get(x).copied().unwrap_or(0);
+                        foreground.blend(glyph_background, intensity)
+                    }
+                    // An underline fills the line spacing below the glyph.
+                    None if underline => color.rgb(),
+                    // Otherwise it keeps the plain background, even in reverse video.
+                    None => background.rgb(),
+                };
+                display.write_pixel(self.region.x + left + x, self.region.y + top + y, color);
+            }
         }
     }
 
-    /// Clears the entire framebuffer.
+    /// Clears the entire screen to the background color.
     pub fn clear(&mut self) {
-        self.x_pos = BORDER_PADDING;
-        self.y_pos = BORDER_PADDING;
-        self.framebuffer.fill(0);
+        self.column = 0;
+        self.row = 0;
+        self.grid.clear(self.blank());
+        self.redraw_all = true;
+    }
+
+    /// Scrolls the view back by a screen into the scrollback.
+    pub fn page_up(&mut self) {
+        self.view_offset = (self.view_offset + self.grid.rows()).min(self.scrollback.line_count());
+    }
+
+    /// Scrolls the view forward by a screen, towards the live text.
+    pub fn page_down(&mut self) {
+        self.view_offset = self.view_offset.saturating_sub(self.grid.rows());
     }
 
-    /// Retrieves the framebuffer width.
+    /// Returns the terminal to its initial state: default attributes, no pending
+    /// escape sequence, no scrollback and a cleared screen.
+    pub fn reset(&mut self) {
+        self.ansi = AnsiParser::new();
+        self.attributes = Attributes::default();
+        self.cursor_visible = true;
+        self.cursor_style = CursorStyle::Block;
+        self.set_cursor_blinking(true);
+        self.scrollback.clear();
+        self.view_offset = 0;
+        self.clear();
+    }
+
+    /// An empty cell in the current colors, used for erasing.
+    fn blank(&self) -> Cell {
+        Cell::blank(Attributes {
+            bold: false,
+            underline: false,
+            reverse: false,
+            ..self.attributes
+        })
+    }
+
+    /// Retrieves the width of the screen area.
     pub fn width(&self) -> usize {
-        self.info.width
+        self.region.width
     }
 
-    /// Retrieves the framebuffer height.
+    /// Retrieves the height of the screen area.
     pub fn height(&self) -> usize {
-        self.info.height
+        self.region.height
     }
 
     /// Advances to a new line.
     pub fn newline(&mut self) {
-        self.y_pos += CHAR_RASTER_HEIGHT.val() + 2;
+        self.echo('\n');
+        self.line_feed();
+    }
+
+    /// Moves the position to the start of the next line, scrolling if needed.
+    fn line_feed(&mut self) {
+        self.row += 1;
         self.carriage_return();
-        if self.y_pos >= self.height() {
+        if self.row >= self.grid.rows() {
             self.scroll();
         }
     }
 
     /// Resets the position to the start of the line.
     fn carriage_return(&mut self) {
-        self.x_pos = BORDER_PADDING;
+        self.column = 0;
     }
 
-    /// Scrolls the screen content upward by one line.
+    /// Scrolls the screen content upward by one line and moves to the last line.
     fn scroll(&mut self) {
-        let row_height = CHAR_RASTER_HEIGHT.val() + 2;
-        let screen_bytes = self.info.width * self.info.bytes_per_pixel;
-        let row_bytes = row_height * self.info.stride * self.info.bytes_per_pixel;
+        self.scroll_grid();
+        self.row = self.grid.rows() - 1;
+        self.pending_scroll += 1;
+    }
+
+    /// Moves the grid up by one line, keeping the line that leaves it in the
+    /// scrollback.
+    fn scroll_grid(&mut self) {
+        self.scrollback.push(self.grid.row(0));
+        self.grid.scroll_up(self.blank());
+    }
 
-        self.framebuffer.copy_within(row_bytes..screen_bytes, 0);
+    /// Moves the drawn lines up along with the text they show.
+    ///
+    /// In a back buffer moving pixels is cheaper than drawing every line again.
+    /// Without one, the changed cells are simply drawn again, which avoids
+    /// reading from video memory.
+    fn scroll_drawn(&mut self, display: &mut Display) {
+        let lines = self.pending_scroll.min(self.grid.rows());
+        let distance = lines * self.font.line_height();
+        let region = self.region;
+        display.scroll_up(region.xs(), region.ys(), distance);
 
-        let start_of_last_row = screen_bytes - row_bytes;
-        self.framebuffer[start_of_last_row..screen_bytes].fill(0);
+        // Clear everything the copy did not overwrite, which covers the new lines.
+        let first_new_line = BORDER_PADDING + (self.grid.rows() - lines) * self.font.line_height();
+        let vacated = region.y + region.height.saturating_sub(distance).min(first_new_line);
+        let blank = self.blank();
+        let background = blank.attributes.background.rgb();
+        display.fill(region.xs(), vacated..region.y + region.height, background);
+        self.fill_margins(display, background);
+        for _ in 0..lines {
+            self.drawn.scroll_up(blank);
+        }
+    }
 
-        self.y_pos = self.height() - row_height - BORDER_PADDING;
+    /// Paints the margins around the grid, which a redraw fills with the current
+    /// background as well.
+    fn fill_margins(&self, display: &mut Display, background: (u8, u8, u8)) {
+        let region = self.region;
+        let (right, bottom) = (region.x + region.width, region.y + region.height);
+        let grid_left = region.x + BORDER_PADDING.min(region.width);
+        let grid_top = region.y + BORDER_PADDING.min(region.height);
+        let grid_right = region.x
+            + (BORDER_PADDING + self.grid.columns() * self.font.char_width()).min(region.width);
+        let grid_bottom = region.y
+            + (BORDER_PADDING + self.grid.rows() * self.font.line_height()).min(region.height);
+        display.fill(region.xs(), region.y..grid_top, background);
+        display.fill(region.xs(), grid_bottom..bottom, background);
+        display.fill(region.x..grid_left, grid_top..grid_bottom, background);
+        display.fill(grid_right..right, grid_top..grid_bottom, background);
     }
 
-    /// Writes a single character to the framebuffer.
-    fn write_char(&mut self, c: char) {
+    /// Writes a single character to the framebuffer, interpreting ANSI escape sequences.
+    pub fn write_char(&mut self, c: char) {
+        // Keep the cursor in sight while output is going on, and the live text.
+        self.blink_on = true;
+        self.view_offset = 0;
+        match self.ansi.advance(c) {
+            Action::Print(c) => self.write_plain_char(c),
+            // Escape sequences are passed through so serial terminals apply them too.
+            Action::None => self.echo(c),
+            Action::Control(sequence) => {
+                self.echo(c);
+                self.execute_control(&sequence);
+            }
+            Action::Escape(c) => {
+                self.echo(c);
+                match c {
+                    '7' => self.save_cursor(),
+                    '8' => self.restore_cursor(),
+                    'c' => self.reset(),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Writes a character that is not part of an escape sequence.
+    fn write_plain_char(&mut self, c: char) {
         match c {
             '\n' => self.newline(),
-            '\r' => self.carriage_return(),
+            '\r' => {
+                self.echo(c);
+                self.carriage_return();
+            }
             '\t' => self.write_tab(),
+            BACKSPACE => {
+                self.echo(c);
+                self.backspace();
+            }
             c => {
-                let new_xpos = self.x_pos + font_constants::CHAR_RASTER_WIDTH;
-                if new_xpos >= self.width() {
-                    self.newline();
+                self.echo(c);
+                if self.column >= self.grid.columns() {
+                    self.line_feed();
+                }
+                let cell = Cell {
+                    ch: c,
+                    attributes: self.attributes,
+                };
+                self.grid.set(self.column, self.row, cell);
+                self.column += 1;
+            }
+        }
+    }
+
+    /// Executes a completed control sequence.
+    fn execute_control(&mut self, sequence: &ControlSequence) {
+        if sequence.private {
+            // Of the private modes, only cursor visibility is supported.
+            if sequence.param(0, 0) == 25 {
+                match sequence.action {
+                    'h' => self.cursor_visible = true,
+                    'l' => self.cursor_visible = false,
+                    _ => {}
+                }
+            }
+            return;
+        }
+        if let Some(intermediate) = sequence.intermediate {
+            // DECSCUSR: 0 to 2 select a block and 3 and 4 an underline cursor,
+            // where odd values blink.
+            if intermediate == ' ' && sequence.action == 'q' {
+                let style = sequence.param(0, 0);
+                self.set_cursor_style(match style {
+                    3 | 4 => CursorStyle::Underline,
+                    _ => CursorStyle::Block,
+                });
+                self.set_cursor_blinking(style <= 1 || style % 2 == 1);
+            }
+            return;
+        }
+        let count = sequence.param(0, 1) as usize;
+        let (column, row) = self.cursor_cell();
+        match sequence.action {
+            'A' => self.move_to_cell(column, row.saturating_sub(count)),
+            'B' => self.move_to_cell(column, row + count),
+            'C' => self.move_to_cell(column + count, row),
+            'D' => self.move_to_cell(column.saturating_sub(count), row),
+            'H' | 'f' => {
+                let row = sequence.param(0, 1) as usize - 1;
+                let column = sequence.param(1, 1) as usize - 1;
+                self.move_to_cell(column, row);
+            }
+            'J' => self.erase_in_display(sequence.param(0, 0)),
+            'K' => self.erase_in_line(sequence.param(0, 0)),
+            'm' => self.select_graphic_rendition(sequence.params()),
+            's' => self.save_cursor(),
+            'u' => self.restore_cursor(),
+            _ => {}
+        }
+    }
+
+    /// Applies SGR parameters to the text attributes.
+    fn select_graphic_rendition(&mut self, params: &[u16]) {
+        if params.is_empty() {
+            self.select_graphic_rendition(&[0]);
+            return;
+        }
+        let mut params = params.iter().copied();
+        while let Some(param) = params.next() {
+            match param {
+                0 => self.attributes = Attributes::default(),
+                1 => self.attributes.bold = true,
+                22 => self.attributes.bold = false,
+                4 => self.attributes.underline = true,
+                10..=19 => {
+                    if let Some(font) = FontConfig::from_sgr(param) {
+                        self.set_font(font);
+                    }
+                }
+                24 => self.attributes.underline = false,
+                7 => self.attributes.reverse = true,
+                27 => self.attributes.reverse = false,
+                30..=37 => self.attributes.color = TextColor::from_ansi((param - 30) as u8),
+                90..=97 => self.attributes.color = TextColor::from_ansi((param - 90 + 8) as u8),
+                39 => self.attributes.color = TextColor::default(),
+                38 => {
+                    if let Some(color) = extended_color(&mut params) {
+                        self.attributes.color = color;
+                    }
                 }
-                let new_ypos = self.y_pos + CHAR_RASTER_HEIGHT.val() + BORDER_PADDING;
-                if new_ypos >= self.height() {
-                    self.scroll();
+                40..=47 => self.attributes.background = TextColor::from_ansi((param - 40) as u8),
+                100..=107 => self.attributes.background = TextColor::from_ansi((param - 100 + 8) as u8),
+                49 => self.attributes.background = TextColor::DEFAULT_BACKGROUND,
+                48 => {
+                    if let Some(color) = extended_color(&mut params) {
+                        self.attributes.background = color;
+                    }
                 }
-                self.write_rendered_char(get_char_raster(c));
+                _ => {}
             }
         }
     }
 
-    /// Writes a tab space.
-    pub fn write_tab(&mut self) {
-        self.x_pos += font_constants::CHAR_RASTER_WIDTH * 4;
-        if self.x_pos >= self.width() {
-            self.newline();
+    /// The column and row of the cursor, kept on the grid while a wrap is pending.
+    fn cursor_cell(&self) -> (usize, usize) {
+        (self.column.min(self.grid.columns() - 1), self.row)
+    }
+
+    /// Moves the cursor to a cell, clamped to the screen.
+    fn move_to_cell(&mut self, column: usize, row: usize) {
+        self.column = column.min(self.grid.columns() - 1);
+        self.row = row.min(self.grid.rows() - 1);
+    }
+
+    /// Erases part of the cursor's line: to its end (0), from its start (1) or all of it (2).
+    fn erase_in_line(&mut self, mode: u16) {
+        let (column, row) = self.cursor_cell();
+        let (columns, blank) = (self.grid.columns(), self.blank());
+        match mode {
+            0 => self.grid.clear_cells(row, column..columns, blank),
+            1 => self.grid.clear_cells(row, 0..column + 1, blank),
+            2 => self.grid.clear_cells(row, 0..columns, blank),
+            _ => {}
         }
     }
 
-    /// Renders a character to the framebuffer.
-    fn write_rendered_char(&mut self, rendered_char: RasterizedChar) {
-        for (y, row) in rendered_char.raster().iter().enumerate() {
-            for (x, byte) in row.iter().enumerate() {
-                self.write_pixel(self.x_pos + x, self.y_pos + y, *byte);
+    /// Erases part of the screen: from the cursor to the end (0), from the start
+    /// to the cursor (1), all of it (2) or all of it and the scrollback (3). The
+    /// cursor does not move.
+    fn erase_in_display(&mut self, mode: u16) {
+        let (_, row) = self.cursor_cell();
+        let (rows, columns, blank) = (self.grid.rows(), self.grid.columns(), self.blank());
+        match mode {
+            0 => {
+                self.erase_in_line(0);
+                for row in row + 1..rows {
+                    self.grid.clear_cells(row, 0..columns, blank);
+                }
+            }
+            1 => {
+                for row in 0..row {
+                    self.grid.clear_cells(row, 0..columns, blank);
+                }
+                self.erase_in_line(1);
             }
+            2 => self.grid.clear(blank),
+            3 => {
+                self.grid.clear(blank);
+                self.scrollback.clear();
+            }
+            _ => {}
         }
-        self.x_pos += rendered_char.width();
-    }
-
-    /// Writes a pixel at the specified position with the given intensity.
-    fn write_pixel(&mut self, x: usize, y: usize, intensity: u8) {
-        let pixel_offset = y * self.info.stride + x;
-        // Choose the color ordering based on pixel format.
-        // Note: The alpha channel is set to 0xFF (opaque).
-        let color = match self.info.pixel_format {
-            // For PixelFormat::Rgb, assume ordering: R, G, B, A.
-            bootloader_api::info::PixelFormat::Rgb => match self.color {
-                TextColor::White  => [intensity, intensity, intensity, 0xFF],
-                TextColor::Red    => [intensity, 0, 0, 0xFF],
-                TextColor::Green  => [0, intensity, 0, 0xFF],
-                TextColor::Blue   => [0, 0, intensity, 0xFF],
-                TextColor::Yellow => [intensity, intensity, 0, 0xFF],
-            },
-            // For PixelFormat::Bgr, assume ordering: B, G, R, A.
-            bootloader_api::info::PixelFormat::Bgr => match self.color {
-                TextColor::White  => [intensity, intensity, intensity, 0xFF],
-                TextColor::Red    => [0, 0, intensity, 0xFF],
-                TextColor::Green  => [0, intensity, 0, 0xFF],
-                TextColor::Blue   => [intensity, 0, 0, 0xFF],
-                TextColor::Yellow => [0, intensity, intensity, 0xFF],
-            },
-            // Fallback for other pixel formats.
-            _ => match self.color {
-                TextColor::White  => [intensity, intensity, intensity, 0xFF],
-                TextColor::Red    => [intensity, 0, 0, 0xFF],
-                TextColor::Green  => [0, intensity, 0, 0xFF],
-                TextColor::Blue   => [0, 0, intensity, 0xFF],
-                TextColor::Yellow => [intensity, intensity, 0, 0xFF],
-            },
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = SavedCursor {
+            column: self.column,
+            row: self.row,
+            attributes: self.attributes,
         };
+    }
 
-        let bytes_per_pixel = self.info.bytes_per_pixel;
-        let byte_offset = pixel_offset * bytes_per_pixel;
-        self.framebuffer[byte_offset..(byte_offset + bytes_per_pixel)]
-            .copy_from_slice(&color[..bytes_per_pixel]);
+    fn restore_cursor(&mut self) {
+        let saved = self.saved_cursor;
+        self.column = saved.column;
+        self.row = saved.row;
+        self.attributes = saved.attributes;
+    }
 
-        unsafe {
-            ptr::read_volatile(&self.framebuffer[byte_offset]);
+    /// Writes a tab space.
+    pub fn write_tab(&mut self) {
+        self.echo('\t');
+        self.column += 4;
+        if self.column >= self.grid.columns() {
+            self.line_feed();
         }
     }
 
+    /// Moves back by one character and erases it, wrapping to the end of the previous line.
+    fn backspace(&mut self) {
+        if self.column > 0 {
+            self.column -= 1;
+        } else if self.row > 0 {
+            // The last glyph of a full line is in the last column.
+            self.row -= 1;
+            self.column = self.grid.columns() - 1;
+        } else {
+            return;
+        }
+        let blank = self.blank();
+        self.grid.set(self.column, self.row, blank);
+    }
+
+    /// Draws a solid bar in the current color across the current line and moves below it.
+    pub fn write_rule(&mut self) {
+        if self.column != 0 {
+            self.newline();
+        }
+        // A blank cell in reverse video is solid text color.
+        let bar = Cell::blank(Attributes {
+            reverse: !self.attributes.reverse,
+            ..self.attributes
+        });
+        for column in 0..self.grid.columns() {
+            self.grid.set(column, self.row, bar);
+        }
+        self.newline();
+    }
+
     /// Changes the text color.
     pub fn set_color(&mut self, color: TextColor) {
-        self.color = color;
+        self.attributes.color = color;
+    }
+
+    /// Retrieves the current text color.
+    pub fn color(&self) -> TextColor {
+        self.attributes.color
+    }
+
+    /// Changes the background color used for new text and erased cells.
+    pub fn set_background(&mut self, background: TextColor) {
+        self.attributes.background = background;
     }
 }
 
-// Safety guarantees for multithreading.
-unsafe impl Send for FrameBufferWriter {}
-unsafe impl Sync for FrameBufferWriter {}
+/// The number of columns and rows of text that fit on a screen area.
+fn grid_size(width: usize, height: usize, font: &FontConfig) -> (usize, usize) {
+    // Glyphs keep a border of `BORDER_PADDING` pixels to the right and bottom edges.
+    let columns = width.saturating_sub(BORDER_PADDING + 1) / font.char_width();
+    let last_row_start = height.saturating_sub(font.char_height() + 2 * BORDER_PADDING);
+    (columns.max(1), last_row_start / font.line_height() + 1)
+}
 
 impl fmt::Write for FrameBufferWriter {
     fn write_str(&mut self, s: &str) -> fmt::Result {
@@ -223,3 +695,180 @@ impl fmt::Write for FrameBufferWriter {
         Ok(())
     }
 }
+
+/// Reads the color arguments following SGR 38 or 48: `5;index` or `2;r;g;b`.
+fn extended_color(params: &mut impl Iterator<Item = u16>) -> Option<TextColor> {
+    let mode = params.next()?;
+    let mut component = || params.next().map(|value| value.min(0xFF) as u8);
+    match mode {
+        5 => component().map(TextColor::from_ansi),
+        2 => Some(TextColor::Rgb(component()?, component()?, component()?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use bootloader_api::info::PixelFormat;
+    use crate::writer::display::TEST_FORMATS;
+    use core::fmt::Write;
+
+    /// A display with a back buffer, four lines of text high plus a few spare
+    /// scanlines, and padding after each scanline.
+    fn display(format: PixelFormat, bytes_per_pixel: usize) -> Display {
+        let font = FontConfig::DEFAULT;
+        let (width, height) = (12 * font.char_width() + 7, 4 * font.line_height() + 5);
+        let mut display = Display::for_test(format, bytes_per_pixel, width, height, width + 9);
+        display.enable_back_buffer().unwrap();
+        display
+    }
+
+    /// The whole display and an inset region, which scrolls part of each scanline.
+    fn regions(display: &Display) -> [Region; 2] {
+        let full = display.region();
+        [full, Region::new(3, 2, full.width - 5, full.height - 3)]
+    }
+
+    fn pixels(display: &mut Display) -> Vec<(u8, u8, u8)> {
+        let (width, height) = (display.width(), display.height());
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| display.read_pixel(x, y))
+            .collect()
+    }
+
+    /// Renders the text through `scroll_drawn` and checks the result against
+    /// drawing the same text from scratch.
+    fn assert_scroll_matches_redraw(first: &str, second: &str) {
+        for (format, bytes_per_pixel) in TEST_FORMATS {
+            for region in regions(&display(format, bytes_per_pixel)) {
+                let mut scrolled = display(format, bytes_per_pixel);
+                let mut writer = FrameBufferWriter::new(region);
+                writer.set_serial_echo(false);
+                writer.write_str(first).unwrap();
+                writer.render(&mut scrolled);
+                writer.write_str(second).unwrap();
+                assert!(writer.pending_scroll > 0);
+                writer.render(&mut scrolled);
+
+                let mut redrawn = display(format, bytes_per_pixel);
+                writer.redraw();
+                writer.render(&mut redrawn);
+                assert!(
+                    pixels(&mut scrolled) == pixels(&mut redrawn),
+                    "{:?} in {:?}",
+                    format,
+                    region
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn scroll_drawn_matches_redraw() {
+        assert_scroll_matches_redraw("one\ntwo\nthree", "\n\x1b[31mfour\n\x1b[1mfive");
+    }
+
+    #[test]
+    fn scroll_drawn_with_a_background_matches_redraw() {
+        assert_scroll_matches_redraw("one\ntwo\nthree", "\x1b[44m\nfour\nfive");
+    }
+
+    #[test]
+    fn scroll_drawn_by_a_whole_screen_matches_redraw() {
+        assert_scroll_matches_redraw("one\ntwo", "\na\nb\nc\nd\ne\nf");
+    }
+
+    /// A terminal with a grid of the given size that does not echo to serial.
+    fn terminal(columns: usize, rows: usize) -> FrameBufferWriter {
+        let font = FontConfig::DEFAULT;
+        let width = columns * font.char_width() + BORDER_PADDING + 1;
+        let height = (rows - 1) * font.line_height() + font.char_height() + 2 * BORDER_PADDING;
+        let mut writer = FrameBufferWriter::new(Region::new(0, 0, width, height));
+        writer.set_serial_echo(false);
+        writer
+    }
+
+    fn rows(writer: &FrameBufferWriter) -> Vec<String> {
+        (0..writer.grid().rows())
+            .map(|row| writer.grid().row_text(row))
+            .collect()
+    }
+
+    #[test]
+    fn text_wraps_at_the_last_column() {
+        let mut writer = terminal(10, 3);
+        writer.write_str("0123456789").unwrap();
+        // The cursor stays on the last column until the next character wraps.
+        assert_eq!(writer.cursor_position(), (9, 0));
+        writer.write_str("ab").unwrap();
+        assert_eq!(rows(&writer), ["0123456789", "ab", ""]);
+        assert_eq!(writer.cursor_position(), (2, 1));
+    }
+
+    #[test]
+    fn newlines_scroll_the_grid() {
+        let mut writer = terminal(10, 3);
+        writer.write_str("one\ntwo\nthree\nfour").unwrap();
+        assert_eq!(rows(&writer), ["two", "three", "four"]);
+        assert_eq!(writer.cursor_position(), (4, 2));
+    }
+
+    #[test]
+    fn tabs_and_backspaces_move_the_cursor() {
+        let mut writer = terminal(10, 3);
+        writer.write_str("a\tbc").unwrap();
+        writer.write_char(BACKSPACE);
+        assert_eq!(rows(&writer)[0], "a    b");
+        assert_eq!(writer.cursor_position(), (6, 0));
+    }
+
+    #[test]
+    fn ansi_sequences_move_and_erase() {
+        let mut writer = terminal(10, 3);
+        writer.write_str("hello\nworld").unwrap();
+        writer.write_str("\x1b[1;3HX\x1b[K").unwrap();
+        assert_eq!(rows(&writer), ["heX", "world", ""]);
+        assert_eq!(writer.cursor_position(), (3, 0));
+        writer.write_str("\x1b[2B\x1b[2D\x1b[31mZ").unwrap();
+        assert_eq!(rows(&writer)[2], " Z");
+        assert_eq!(writer.cursor_position(), (2, 2));
+        writer.write_str("\x1b[2J").unwrap();
+        assert_eq!(rows(&writer), ["", "", ""]);
+        assert_eq!(writer.cursor_position(), (2, 2));
+    }
+
+    #[test]
+    fn saved_cursor_is_restored() {
+        let mut writer = terminal(10, 3);
+        writer.write_str("ab\x1b7\ncd\x1b8e").unwrap();
+        assert_eq!(rows(&writer), ["abe", "cd", ""]);
+        assert_eq!(writer.cursor_position(), (3, 0));
+    }
+
+    #[test]
+    fn markup_escapes_reach_the_grid() {
+        let mut writer = terminal(10, 3);
+        crate::writer::write_markup(&mut writer, r"a\tb\n\cRed1\\d\bBlue\q").unwrap();
+        assert_eq!(rows(&writer), ["a    b", r"1\d\q", ""]);
+        assert_eq!(writer.cursor_position(), (5, 1));
+        assert_eq!(writer.grid().row(1)[0].attributes.color, TextColor::Red);
+        assert_eq!(writer.grid().row(1)[3].attributes.background, TextColor::Blue);
+    }
+
+    #[test]
+    fn cursor_position_is_set_in_pixels() {
+        let mut writer = terminal(10, 3);
+        let font = FontConfig::DEFAULT;
+        let (x, y) = (3 * font.char_width() + BORDER_PADDING, font.line_height() + 2);
+        writer.set_cursor_position(x as isize, y as isize);
+        assert_eq!(writer.cursor_position(), (3, 1));
+        writer.set_cursor_position(-1, 50);
+        assert_eq!(writer.cursor_position(), (0, 0));
+        writer.set_cursor_position(10_000, 10_000);
+        assert_eq!(writer.cursor_position(), (9, 2));
+    }
+}
@@ -13,6 +13,8 @@ fn main() {
     } else {
         cmd.arg("-drive").arg(format!("format=raw,file={bios_path}"));
     }
+    // forward the kernel's COM1 output to the host terminal
+    cmd.arg("-serial").arg("stdio");
     let mut child = cmd.spawn().unwrap();
     child.wait().unwrap();
     